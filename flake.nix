@@ -36,6 +36,8 @@
             packages = with pkgs; [
               (rust-bin.selectLatestNightlyWith
                 (toolchain: toolchain.default))
+              # dbus-run-session for the DBus tests
+              dbus
             ];
           };
 
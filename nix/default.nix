@@ -41,6 +41,9 @@ rustPlatform.buildRustPackage rec {
 
   copyLibs = true;
 
+  # the DBus tests need dbus-run-session, CI runs them in the dev shell instead
+  doCheck = false;
+
   meta = with lib; {
     description = "A work in progress notification daemon made with rust and gtk.";
     homepage = "https://github.com/DashieTM/OxiDash";
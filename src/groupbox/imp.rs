@@ -0,0 +1,29 @@
+use std::cell::{Cell, RefCell};
+
+use gtk::subclass::prelude::*;
+use gtk::{glib, Button, Image, Label};
+
+#[derive(Default)]
+pub struct GroupBox {
+    pub key: RefCell<String>,
+    pub icon: RefCell<Image>,
+    pub name: RefCell<Label>,
+    pub count: RefCell<Label>,
+    pub collapse_button: RefCell<Button>,
+    pub clear_button: RefCell<Button>,
+    pub list: RefCell<gtk::Box>,
+    pub collapsed: Cell<bool>,
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for GroupBox {
+    const NAME: &'static str = "GroupBox";
+    type Type = super::GroupBox;
+    type ParentType = gtk::Box;
+}
+
+impl ObjectImpl for GroupBox {}
+
+impl WidgetImpl for GroupBox {}
+
+impl BoxImpl for GroupBox {}
@@ -0,0 +1,26 @@
+pub mod imp;
+
+use glib::Object;
+use gtk::{gio, glib};
+
+glib::wrapper! {
+    pub struct GroupBox(ObjectSubclass<imp::GroupBox>)
+        @extends gtk::Box, gtk::Widget,
+        @implements gio::ActionGroup, gio::ActionMap, gtk::Accessible, gtk::Buildable,
+                    gtk::ConstraintTarget;
+}
+
+impl GroupBox {
+    pub fn new(orientation: gtk::Orientation, spacing: i32) -> Self {
+        Object::builder()
+            .property("orientation", orientation)
+            .property("spacing", spacing)
+            .build()
+    }
+}
+
+impl Default for GroupBox {
+    fn default() -> Self {
+        Object::builder().build()
+    }
+}
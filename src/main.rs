@@ -1,29 +1,34 @@
-#![feature(string_remove_matches)]
+mod groupbox;
 mod notibox;
 mod utils;
 mod window;
 
-use dbus::blocking::Connection;
+use dbus::arg::{PropMap, Variant};
 use directories_next as dirs;
 use gtk::subclass::prelude::ObjectSubclassIsExt;
-use std::cell::RefCell;
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::fmt::Display;
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::time::Duration;
+use std::str::FromStr;
 use std::{env, fs, thread};
-use utils::listener::run;
+use utils::cli::{self, Cli, Command};
+use utils::client::{Client, Reply, Server};
+use utils::config::{self, Config, KeyAction, Layout, Monitor};
+use utils::{daemon, dnd, time};
+use utils::listener::{self, Event};
 use utils::NotificationButton;
-use window::imp::{check_duplicates, modify_notification, resize_window, show_notification};
+use window::imp::{
+    check_duplicates, modify_notification, refresh_times, resize_window, set_grouping,
+    show_notification,
+};
 
-use gtk::gdk::Key;
 use gtk::gio::SimpleAction;
 use gtk::glib::{clone, ExitCode, MainContext};
 use gtk::prelude::*;
 use gtk::{gio, glib, Application};
-use gtk4_layer_shell::Edge;
 use window::Window;
 
 const APP_ID: &str = "org.dashie.oxidash";
@@ -53,27 +58,58 @@ impl ImageData {
     }
 }
 
-#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Debug)]
+#[derive(Clone, Default, Eq, PartialEq, PartialOrd, Ord, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Urgency {
     Low,
+    #[default]
     Normal,
     Urgent,
 }
 
+// The Desktop Notifications spec numbers urgencies 0, 1 and 2, oxinoti
+// sends 1, 2 and 3 instead. Oxinoti is what OxiDash runs next to, so its
+// encoding is the default. The urgency hint always uses spec values.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub enum UrgencyEncoding {
+    Spec,
+    #[default]
+    Oxinoti,
+}
+
+impl FromStr for UrgencyEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spec" => Ok(UrgencyEncoding::Spec),
+            "oxinoti" => Ok(UrgencyEncoding::Oxinoti),
+            _ => Err(format!(
+                "invalid urgency encoding {}, only spec or oxinoti allowed",
+                s
+            )),
+        }
+    }
+}
+
 impl Urgency {
-    fn from_i32(value: i32) -> Result<Urgency, &'static str> {
-        match value {
-            1 => Ok(Urgency::Low),
-            2 => Ok(Urgency::Normal),
-            3 => Ok(Urgency::Urgent),
-            _ => Err("invalid number, only 1,2 or 3 allowed"),
+    // unknown values are treated as normal, as the spec asks for
+    pub fn from_i32(value: i32, encoding: UrgencyEncoding) -> Urgency {
+        match (encoding, value) {
+            (UrgencyEncoding::Spec, 0) | (UrgencyEncoding::Oxinoti, 1) => Urgency::Low,
+            (UrgencyEncoding::Spec, 2) | (UrgencyEncoding::Oxinoti, 3) => Urgency::Urgent,
+            _ => Urgency::Normal,
         }
     }
-    fn to_i32(&self) -> i32 {
-        match self {
-            Urgency::Low => 1,
-            Urgency::Normal => 2,
-            Urgency::Urgent => 3,
+    pub fn to_i32(&self, encoding: UrgencyEncoding) -> i32 {
+        let value = match self {
+            Urgency::Low => 0,
+            Urgency::Normal => 1,
+            Urgency::Urgent => 2,
+        };
+        match encoding {
+            UrgencyEncoding::Spec => value,
+            UrgencyEncoding::Oxinoti => value + 1,
         }
     }
     pub fn to_str(&self) -> &str {
@@ -85,13 +121,34 @@ impl Urgency {
     }
 }
 
+// the name, numbers depend on the encoding
 impl Display for Urgency {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.to_i32())
+        let name = match self {
+            Urgency::Low => "low",
+            Urgency::Normal => "normal",
+            Urgency::Urgent => "urgent",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct Action {
+    pub key: String,
+    pub label: String,
+}
+
+impl Action {
+    pub fn is_default(&self) -> bool {
+        self.key == "default"
+    }
+    pub fn is_inline_reply(&self) -> bool {
+        self.key == "inline-reply"
     }
 }
 
-#[derive(Eq, PartialEq, PartialOrd, Ord, Debug)]
+#[derive(Debug)]
 pub struct Notification {
     pub app_name: String,
     pub replaces_id: u32,
@@ -104,89 +161,79 @@ pub struct Notification {
     pub image_path: String,
     pub progress: i32,
     pub image_data: ImageData,
+    pub hints: PropMap,
+    pub received: i64,
 }
 
-impl Notification {
-    pub fn create(
-        app_name: String,
-        replaces_id: u32,
-        app_icon: String,
-        summary: String,
-        body: String,
-        actions: Vec<String>,
-        expire_timeout: i32,
-        urgency: i32,
-        image_path: String,
-        progress: i32,
-        image_data: ImageData,
-    ) -> Self {
+// PropMap holds boxed RefArgs, which can only be cloned through box_clone
+impl Clone for Notification {
+    fn clone(&self) -> Self {
         Self {
-            app_name,
-            replaces_id,
-            app_icon,
-            summary,
-            body,
-            actions,
-            expire_timeout,
-            urgency: Urgency::from_i32(urgency).unwrap_or_else(|_| Urgency::Low),
-            image_path,
-            progress,
-            image_data,
+            app_name: self.app_name.clone(),
+            replaces_id: self.replaces_id,
+            app_icon: self.app_icon.clone(),
+            summary: self.summary.clone(),
+            body: self.body.clone(),
+            actions: self.actions.clone(),
+            expire_timeout: self.expire_timeout,
+            urgency: self.urgency.clone(),
+            image_path: self.image_path.clone(),
+            progress: self.progress,
+            image_data: self.image_data.clone(),
+            hints: self
+                .hints
+                .iter()
+                .map(|(key, value)| (key.clone(), Variant(value.0.box_clone())))
+                .collect(),
+            received: self.received,
         }
     }
 }
 
-fn get_notifications() -> Vec<Notification> {
-    let mut notifications = Vec::new();
-    let conn = Connection::new_session().unwrap();
-    let proxy = conn.with_proxy(
-        "org.freedesktop.Notifications",
-        "/org/freedesktop/Notifications",
-        Duration::from_millis(1000),
-    );
-    let (res,): (
-        Vec<(
-            String,
-            u32,
-            String,
-            String,
-            String,
-            Vec<String>,
-            i32,
-            i32,
-            String,
-            i32,
-            (i32, i32, i32, bool, i32, i32, Vec<u8>),
-        )>,
-    ) = proxy
-        .method_call("org.freedesktop.Notifications", "GetAllNotifications", ())
-        .unwrap_or_else(|_| (Vec::new(),));
-    for notification in res {
-        let raw_data = notification.10;
-        let image_data = ImageData {
-            width: raw_data.0,
-            height: raw_data.1,
-            rowstride: raw_data.2,
-            has_alpha: raw_data.3,
-            bits_per_sample: raw_data.4,
-            channels: raw_data.5,
-            data: raw_data.6,
-        };
-        notifications.push(Notification::create(
-            notification.0,
-            notification.1,
-            notification.2,
-            notification.3,
-            notification.4,
-            notification.5,
-            notification.6,
-            notification.7,
-            notification.8,
-            notification.9,
-            image_data,
-        ));
-    }
-    notifications
+impl Notification {
+    pub fn hint_str(&self, name: &str) -> Option<&str> {
+        self.hints.get(name).and_then(|hint| hint.0.as_str())
+    }
+    pub fn hint_i64(&self, name: &str) -> Option<i64> {
+        self.hints.get(name).and_then(|hint| hint.0.as_i64())
+    }
+    pub fn hint_bool(&self, name: &str) -> bool {
+        self.hint_i64(name).map_or(false, |value| value != 0)
+    }
+    pub fn category(&self) -> Option<&str> {
+        self.hint_str("category")
+    }
+    pub fn desktop_entry(&self) -> Option<&str> {
+        self.hint_str("desktop-entry")
+    }
+    pub fn transient(&self) -> bool {
+        self.hint_bool("transient")
+    }
+    pub fn resident(&self) -> bool {
+        self.hint_bool("resident")
+    }
+    pub fn action_icons(&self) -> bool {
+        self.hint_bool("action-icons")
+    }
+    pub fn value(&self) -> Option<i32> {
+        self.hint_i64("value").map(|value| value.clamp(0, 100) as i32)
+    }
+    pub fn current_progress(&self) -> i32 {
+        if self.progress > -1 {
+            return self.progress;
+        }
+        self.value().unwrap_or(-1)
+    }
+
+    pub fn parsed_actions(&self) -> Vec<Action> {
+        self.actions
+            .chunks(2)
+            .map(|pair| Action {
+                key: pair[0].clone(),
+                label: pair.get(1).cloned().unwrap_or_else(|| pair[0].clone()),
+            })
+            .collect()
+    }
 }
 
 fn create_config_dir() -> PathBuf {
@@ -214,123 +261,294 @@ fn create_config_dir() -> PathBuf {
 }
 
 fn main() -> glib::ExitCode {
-    let toggle = toggle_notification_center();
-    if !toggle {
-        Command::new("killall")
-            .arg("-9")
-            .arg("oxidash")
-            .spawn()
-            .expect("Could not close oxidash");
-        return glib::ExitCode::SUCCESS;
-    }
-
-    let mut css_string = "".to_string();
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 1 {
-        let mut argiter = args.iter();
-        argiter.next().unwrap();
-        match argiter.next().unwrap().as_str() {
-            "--css" => {
-                let next = argiter.next();
-                if next.is_some() {
-                    css_string = next.unwrap().clone();
-                }
-            }
-            _ => {
-                print!(
-                    "usage:
-    --css: use a specific path to load a css style sheet.
-    --help: show this message.\n"
-                );
-                return ExitCode::FAILURE;
-            }
+    let cli = match cli::parse(env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(error) => {
+            eprint!("{}\n\n{}", error, cli::USAGE);
+            return ExitCode::FAILURE;
         }
-    } else {
-        css_string = create_config_dir().to_str().unwrap().into();
+    };
+    if cli.command == Command::Help {
+        print!("{}", cli::USAGE);
+        return ExitCode::SUCCESS;
     }
+    cli::set_verbose(cli.verbose);
+    let config = match load_config(&cli) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(code) = cli::run_headless(&cli, config.behaviour.urgency_encoding) {
+        return code;
+    }
+    let action = cli.command.action().unwrap();
+    let css_string = cli
+        .css
+        .unwrap_or_else(|| create_config_dir().to_str().unwrap().into());
 
     gio::resources_register_include!("src.templates.gresource")
         .expect("Failed to register resources.");
 
     let app = Application::builder().application_id(APP_ID).build();
-
+    // register emits startup on the primary instance
     app.connect_startup(move |_| {
         adw::init().unwrap();
-        load_css(&css_string);
     });
+    if let Err(error) = app.register(gio::Cancellable::NONE) {
+        eprintln!("Could not register oxidash: {}", error);
+        return ExitCode::FAILURE;
+    }
+    // another oxidash is already running, it does the work
+    if app.is_remote() {
+        if cli::verbose() {
+            eprintln!("sending {} to the running oxidash", action);
+        }
+        app.activate_action(action, None);
+        if let Some(connection) = app.dbus_connection() {
+            let _ = connection.flush_sync(gio::Cancellable::NONE);
+        }
+        return ExitCode::SUCCESS;
+    }
+    let resident = cli.resident();
+    if action == "close" && !resident {
+        return ExitCode::SUCCESS;
+    }
+
+    let server = RefCell::new(None);
+    if cli.standalone {
+        match daemon::claim() {
+            Ok(connection) => *server.borrow_mut() = Some(connection),
+            Err(error) => {
+                eprintln!("Could not claim org.freedesktop.Notifications: {}", error);
+                return ExitCode::FAILURE;
+            }
+        }
+    } else if cli.daemon {
+        // the window starts hidden
+        if toggle_notification_center() {
+            toggle_notification_center();
+        }
+    } else if !toggle_notification_center() {
+        // oxinoti still thinks a previous dashboard is open, this is the only one
+        toggle_notification_center();
+    }
 
-    app.connect_activate(build_ui);
-    app.run_with_args(&[""])
+    // a resident dashboard and the standalone server outlive the window
+    let _hold = resident.then(|| app.hold());
+    app.connect_activate(move |app| {
+        if !app.windows().is_empty() {
+            app.activate_action("open", None);
+            return;
+        }
+        build_ui(app, &cli, config.clone(), &css_string, server.take());
+    });
+    app.run_with_args::<&str>(&[])
 }
 
-fn build_ui(app: &Application) {
+fn build_ui(
+    app: &Application,
+    cli: &Cli,
+    config: Config,
+    css_string: &str,
+    server: Option<dbus::blocking::Connection>,
+) {
+    let resident = cli.resident();
+    // the listener and the client keep the encoding they started with
+    let encoding = config.behaviour.urgency_encoding;
     let (tx, rx) = MainContext::channel(glib::PRIORITY_DEFAULT);
-    thread::spawn(move || {
-        run(tx);
-    });
+    let standalone = server.is_some();
+    if let Some(connection) = server {
+        let sender = tx.clone();
+        thread::spawn(move || {
+            if let Err(error) = daemon::serve(connection, sender, encoding) {
+                eprintln!("Notification server stopped: {}", error);
+            }
+        });
+    }
     let window = Window::new(app);
+    window.imp().standalone.set(standalone);
+    window.imp().resident.set(resident);
+    window.imp().config.replace(config.clone());
+    let listening = match listener::start(tx, encoding) {
+        Ok(properties) => {
+            window.imp().properties.set(properties.clone()).ok();
+            window.connect_visible_notify(clone!(@strong properties => move |window| {
+                properties.update(|state| state.visible = window.is_visible());
+            }));
+            window
+                .imp()
+                .button
+                .connect_active_notify(clone!(@strong properties => move |button| {
+                    properties.update(|state| state.do_not_disturb = button.is_active());
+                }));
+            true
+        }
+        Err(error) => {
+            window.imp().show_error(&format!(
+                "Could not listen for notifications: {}",
+                error.message().unwrap_or("unknown error")
+            ));
+            false
+        }
+    };
     window.set_vexpand(true);
-    window.set_default_size(300, 50);
+    window.set_default_size(config.layout.width, 50);
+    window
+        .imp()
+        .sort_dropdown
+        .set_selected(config.behaviour.sort.index());
     let action_close = SimpleAction::new("close", None);
     let delete_notifications = SimpleAction::new("delete_notifications", None);
+    let delete_pinned_notifications = SimpleAction::new("delete_pinned_notifications", None);
     let do_not_disturb = SimpleAction::new("do_not_disturb", None);
+    let timed_do_not_disturb =
+        SimpleAction::new("timed_do_not_disturb", Some(glib::VariantTy::STRING));
+    let toggle_grouping = SimpleAction::new("toggle_grouping", None);
 
     let id_map = Rc::new(RefCell::new(HashMap::<u32, Rc<NotificationButton>>::new()));
     let map_clone = id_map.clone();
-    let notifications = get_notifications();
-    let windowimp = window.imp();
-    for notification in notifications {
-        show_notification(&notification, &windowimp, id_map.clone());
-    }
-
-    delete_notifications.connect_activate(clone!(@weak window => move |_, _| {
-        thread::spawn(|| {
-            let conn = Connection::new_session().unwrap();
-            let proxy = conn.with_proxy(
-                "org.freedesktop.Notifications",
-                "/org/freedesktop/Notifications",
-                Duration::from_millis(1000),
-            );
-            let _: Result<(), dbus::Error> =
-                proxy.method_call("org.freedesktop.Notifications", "RemoveAllNotifications", ());
-        });
-        loop {
-            let child = window.imp().notibox.first_child();
-            if (child).is_none() {
-                break;
+    let (reply_tx, reply_rx) = MainContext::channel(glib::PRIORITY_DEFAULT);
+    let client = Client::new(reply_tx, encoding);
+    window.imp().client.set(client.clone()).ok();
+    // otherwise the listener asks for them once it knows the server is there
+    if !listening {
+        client.get_all();
+        client.get_dnd();
+    }
+    if let Some(until) = dnd::deadline() {
+        dnd::schedule_end(&client, until, dnd_ended(&window));
+    }
+    reply_rx.attach(
+        None,
+        clone!(@weak window, @strong id_map => @default-return glib::Continue(false), move |reply| {
+            match reply {
+                Reply::Notifications(notifications) => {
+                    for notification in notifications {
+                        if check_duplicates(&notification, id_map.clone()) {
+                            modify_notification(notification, window.imp(), id_map.clone());
+                            window.imp().update_counts(&id_map.borrow());
+                        } else {
+                            show_notification(&notification, window.imp(), id_map.clone());
+                        }
+                    }
+                    resize_window(&window);
+                }
+                Reply::DoNotDisturb(enabled) => {
+                    // timed do not disturb needs to know the state to turn it off again
+                    window.imp().dnd_menu_button.set_visible(enabled.is_some());
+                    if let Some(enabled) = enabled {
+                        window.imp().button.set_active(enabled);
+                    }
+                }
+                Reply::Failed(error) => window.imp().show_error(&error.to_string()),
             }
-            window.imp().notibox.remove(&child.unwrap());
-        }
+            glib::Continue(true)
+        }),
+    );
+
+    let time_map = id_map.clone();
+    window.connect_show(clone!(@strong time_map => move |_| {
+        refresh_times(&time_map.borrow());
     }));
+    glib::timeout_add_seconds_local(
+        30,
+        clone!(@weak window => @default-return glib::Continue(false), move || {
+            if window.is_visible() {
+                refresh_times(&time_map.borrow());
+            }
+            glib::Continue(true)
+        }),
+    );
 
-    do_not_disturb.connect_activate(|_, _| {
-        thread::spawn(|| {
-            let conn = Connection::new_session().unwrap();
-            let proxy = conn.with_proxy(
-                "org.freedesktop.Notifications",
-                "/org/freedesktop/Notifications",
-                Duration::from_millis(1000),
-            );
-            let _: Result<(), dbus::Error> =
-                proxy.method_call("org.freedesktop.Notifications", "DoNotDisturb", ());
-        });
+    delete_notifications.connect_activate(clone!(@weak window, @strong id_map => move |_, _| {
+        window.imp().clear_notifications(false, id_map.clone());
+    }));
+
+    delete_pinned_notifications.connect_activate(clone!(@weak window, @strong id_map => move |_, _| {
+        window.imp().clear_notifications(true, id_map.clone());
+    }));
+
+    toggle_grouping.connect_activate(clone!(@weak window, @strong id_map => move |_, _| {
+        let windowimp = window.imp();
+        set_grouping(windowimp, windowimp.group_button.is_active(), id_map.clone());
+    }));
+
+    let dnd_client = client.clone();
+    do_not_disturb.connect_activate(move |_, _| {
+        dnd::set_deadline(None);
+        dnd_client.toggle_dnd();
     });
 
+    timed_do_not_disturb.connect_activate(clone!(@weak window => move |_, parameter| {
+        let until = match parameter.and_then(|parameter| parameter.str()) {
+            Some("hour") => time::now() + 60 * 60,
+            Some("tomorrow") => time::start_of_tomorrow(),
+            _ => return,
+        };
+        window.imp().dnd_menu_button.popdown();
+        dnd::start(&client, until, dnd_ended(&window));
+    }));
+
     action_close.connect_activate(clone!(@weak window => move |_, _| {
-        toggle_notification_center();
-        window.close();
+        close_window(&window);
     }));
 
     window.add_action(&action_close);
     window.add_action(&delete_notifications);
+    window.add_action(&delete_pinned_notifications);
     window.add_action(&do_not_disturb);
+    window.add_action(&timed_do_not_disturb);
+    window.add_action(&toggle_grouping);
+
+    // activated by later oxidash invocations through the primary instance
+    let app_open = SimpleAction::new("open", None);
+    app_open.connect_activate(clone!(@weak window => move |_, _| {
+        open_window(&window);
+    }));
+    let app_close = SimpleAction::new("close", None);
+    app_close.connect_activate(clone!(@weak window => move |_, _| {
+        close_window(&window);
+    }));
+    let app_toggle = SimpleAction::new("toggle", None);
+    app_toggle.connect_activate(clone!(@weak window => move |_, _| {
+        if window.is_visible() {
+            close_window(&window);
+        } else {
+            open_window(&window);
+        }
+    }));
+    app.add_action(&app_open);
+    app.add_action(&app_close);
+    app.add_action(&app_toggle);
 
     gtk4_layer_shell::init_for_window(&window);
-    gtk4_layer_shell::set_keyboard_mode(&window, gtk4_layer_shell::KeyboardMode::Exclusive);
-    gtk4_layer_shell::auto_exclusive_zone_enable(&window);
-    gtk4_layer_shell::set_layer(&window, gtk4_layer_shell::Layer::Overlay);
-    gtk4_layer_shell::set_anchor(&window, Edge::Right, true);
-    gtk4_layer_shell::set_anchor(&window, Edge::Top, true);
+    apply_layout(&window, &config.layout);
+
+    let css_provider = load_css(&window, css_string);
+    if !css_string.is_empty() {
+        let css_path = PathBuf::from(css_string);
+        // parsing errors are reported while loading
+        let failed = Rc::new(Cell::new(false));
+        css_provider.connect_parsing_error(clone!(@strong failed => move |_, _, _| {
+            failed.set(true);
+        }));
+        watch(&window, Path::new(css_string), move |window| {
+            failed.set(false);
+            css_provider.load_from_path(&css_path);
+            if !failed.get() {
+                window.imp().file_loaded(&css_path);
+            }
+        });
+    }
+    if let Some(config_path) = cli.config.clone().or_else(config::default_path) {
+        let cli = cli.clone();
+        let path = config_path.clone();
+        watch(&window, &config_path, move |window| {
+            reload_config(window, &cli, &path)
+        });
+    }
 
     let windowrc = Rc::new(window.clone());
     let windowrc1 = windowrc.clone();
@@ -339,8 +557,7 @@ fn build_ui(app: &Application) {
 
     let focus_event_controller = gtk::EventControllerFocus::new();
     focus_event_controller.connect_leave(move |_| {
-        toggle_notification_center();
-        windowrc.close();
+        close_window(&windowrc);
     });
 
     let gesture = gtk::GestureClick::new();
@@ -348,40 +565,59 @@ fn build_ui(app: &Application) {
 
     gesture.connect_pressed(move |_gesture, _, _, _| {
         if !windowrc1.imp().has_pointer.get() {
-            toggle_notification_center();
-            windowrc1.close();
+            close_window(&windowrc1);
         }
     });
 
+    let key_map = id_map.clone();
     let key_event_controller = gtk::EventControllerKey::new();
-    key_event_controller.connect_key_pressed(move |_controller, key, _keycode, _state| match key {
-        Key::Escape => {
-            toggle_notification_center();
-            windowrc2.close();
-            gtk::Inhibit(true)
-        }
-        Key::_1 => {
-            do_not_disturb.activate(None);
-            gtk::Inhibit(true)
+    key_event_controller.connect_key_pressed(move |_controller, key, _keycode, _state| {
+        let windowimp = windowrc2.imp();
+        let binding = windowimp.config.borrow().binding(key);
+        match binding {
+            Some(KeyAction::Close) => close_window(&windowrc2),
+            Some(KeyAction::ToggleDnd) => do_not_disturb.activate(None),
+            Some(KeyAction::ClearAll) => delete_notifications.activate(None),
+            Some(KeyAction::FocusNext) => windowimp.move_focus(true),
+            Some(KeyAction::FocusPrevious) => windowimp.move_focus(false),
+            Some(KeyAction::DismissFocused) => windowimp.dismiss_focused(key_map.clone()),
+            Some(KeyAction::ActivateDefaultAction) => windowimp.activate_focused(key_map.clone()),
+            Some(KeyAction::Expand) => windowimp.expand_focused(),
+            Some(KeyAction::StartInlineReply) => windowimp.reply_to_focused(),
+            None => return gtk::Inhibit(false),
         }
-        Key::_2 => {
-            toggle_notification_center();
-            windowrc2.close();
-            gtk::Inhibit(true)
-        }
-        Key::_3 => {
-            delete_notifications.activate(None);
-            gtk::Inhibit(true)
-        }
-        _ => gtk::Inhibit(false),
+        gtk::Inhibit(true)
     });
 
-    rx.attach(None, move |notification| {
-        if check_duplicates(&notification, map_clone.clone()) {
-            modify_notification(notification, &windowrc3.imp(), map_clone.clone());
-        } else {
-            show_notification(&notification, &windowrc3.imp(), map_clone.clone());
-            resize_window(&windowrc3);
+    rx.attach(None, move |event| {
+        match event {
+            Event::Notify(notification) => {
+                if check_duplicates(&notification, map_clone.clone()) {
+                    modify_notification(notification, &windowrc3.imp(), map_clone.clone());
+                    windowrc3.imp().update_counts(&map_clone.borrow());
+                } else {
+                    show_notification(&notification, &windowrc3.imp(), map_clone.clone());
+                    resize_window(&windowrc3);
+                }
+            }
+            Event::Remove(id) => windowrc3
+                .imp()
+                .remove_notification_with_id(id, map_clone.clone()),
+            Event::RemoveAll => windowrc3.imp().remove_all_notifications(map_clone.clone()),
+            Event::DoNotDisturb(enabled) => windowrc3.imp().button.set_active(enabled),
+            Event::Server(running) => {
+                let windowimp = windowrc3.imp();
+                windowimp.server_banner.set_visible(!running);
+                // a restarted server starts over, so its old ids mean nothing anymore
+                if running {
+                    windowimp.remove_all_notifications(map_clone.clone());
+                    windowimp.client().get_all();
+                    windowimp.client().get_dnd();
+                }
+            }
+            Event::CenterTaken => windowrc3.imp().show_error(
+                "Another program owns org.freedesktop.NotificationCenter, new notifications will not show up",
+            ),
         }
         glib::Continue(true)
     });
@@ -389,11 +625,81 @@ fn build_ui(app: &Application) {
     window.add_controller(focus_event_controller);
     window.add_controller(gesture);
     resize_window(&window);
-    window.present();
+    if !resident {
+        window.present();
+    }
 }
 
-fn load_css(css_string: &String) {
+fn load_config(cli: &Cli) -> Result<Config, String> {
+    let mut config = config::load(cli.config.as_deref())?;
+    cli.override_config(&mut config);
+    Ok(config)
+}
+
+fn apply_layout(window: &Window, layout: &Layout) {
+    gtk4_layer_shell::set_keyboard_mode(window, layout.keyboard_mode.into());
+    gtk4_layer_shell::set_layer(window, layout.layer.into());
+    gtk4_layer_shell::set_namespace(window, &layout.namespace);
+    if layout.exclusive_zone {
+        gtk4_layer_shell::auto_exclusive_zone_enable(window);
+    } else {
+        gtk4_layer_shell::set_exclusive_zone(window, 0);
+    }
+    let anchors = layout.position.anchors();
+    for (edge, margin) in layout.margins.edges() {
+        gtk4_layer_shell::set_anchor(window, edge, anchors.contains(&edge));
+        gtk4_layer_shell::set_margin(window, edge, margin);
+    }
+    // the layer shell can't go back to the focused monitor, that takes a restart
+    if let Monitor::Named(name) = &layout.monitor {
+        match find_monitor(name) {
+            Some(monitor) => gtk4_layer_shell::set_monitor(window, &monitor),
+            None => window
+                .imp()
+                .show_error(&format!("Monitor {} is not connected", name)),
+        }
+    }
+}
+
+fn find_monitor(connector: &str) -> Option<gtk::gdk::Monitor> {
+    let monitors = gtk::gdk::Display::default()?.monitors();
+    (0..monitors.n_items())
+        .filter_map(|index| monitors.item(index)?.downcast::<gtk::gdk::Monitor>().ok())
+        .find(|monitor| monitor.connector().as_deref() == Some(connector))
+}
+
+// A broken config keeps the old one, the error shows up in the window.
+fn reload_config(window: &Window, cli: &Cli, path: &Path) {
+    let config = match load_config(cli) {
+        Ok(config) => config,
+        Err(error) => {
+            window.imp().show_file_error(path, &error);
+            return;
+        }
+    };
+    window.imp().file_loaded(path);
+    let old = window.imp().config.replace(config.clone());
+    // a sort picked in the dropdown stays until the config changes it
+    if config.behaviour.sort != old.behaviour.sort {
+        window
+            .imp()
+            .sort_dropdown
+            .set_selected(config.behaviour.sort.index());
+    }
+    window.set_default_size(config.layout.width, 50);
+    apply_layout(window, &config.layout);
+    resize_window(window);
+}
+
+fn load_css(window: &Window, css_string: &str) -> gtk::CssProvider {
     let context_provider = gtk::CssProvider::new();
+    let path = PathBuf::from(css_string);
+    context_provider.connect_parsing_error(clone!(@weak window => move |_, section, error| {
+        window.imp().show_file_error(
+            &path,
+            &format!("Invalid css {}: {}", section.to_str(), error.message()),
+        );
+    }));
     if css_string != "" {
         context_provider.load_from_path(css_string);
     }
@@ -403,22 +709,138 @@ fn load_css(css_string: &String) {
         &context_provider,
         gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
     );
+    context_provider
+}
+
+// Calls on_change whenever the file was written or replaced, the file does not have to exist yet.
+fn watch(window: &Window, path: &Path, on_change: impl Fn(&Window) + 'static) {
+    let file = gio::File::for_path(path);
+    let flags = gio::FileMonitorFlags::WATCH_MOVES;
+    let monitor = match file.monitor_file(flags, gio::Cancellable::NONE) {
+        Ok(monitor) => monitor,
+        Err(error) => {
+            window
+                .imp()
+                .show_error(&format!("Could not watch {}: {}", path.display(), error));
+            return;
+        }
+    };
+    monitor.connect_changed(clone!(@weak window => move |_, _, other, event| {
+        let changed = match event {
+            gio::FileMonitorEvent::ChangesDoneHint | gio::FileMonitorEvent::MovedIn => true,
+            // editors often save to a temporary file and move it over the old one
+            gio::FileMonitorEvent::Renamed => other.map_or(false, |other| other.equal(&file)),
+            _ => false,
+        };
+        if changed {
+            on_change(&window);
+        }
+    }));
+    window.imp().monitors.borrow_mut().push(monitor);
 }
 
 fn toggle_notification_center() -> bool {
-    let conn = Connection::new_session().unwrap();
-    let proxy = conn.with_proxy(
-        "org.freedesktop.Notifications",
-        "/org/freedesktop/Notifications",
-        Duration::from_millis(1000),
-    );
-    let res: Result<(bool,), dbus::Error> = proxy.method_call(
-        "org.freedesktop.Notifications",
-        "ToggleNotificationCenter",
-        (),
-    );
-    if res.is_ok() {
-        return res.unwrap().0;
+    Server::connect()
+        .and_then(|server| server.toggle_notification_center())
+        .unwrap_or(false)
+}
+
+// Only a hidden resident window has to tell oxinoti, a new one already did on startup.
+// It also catches up on what arrived while it was hidden.
+fn open_window(window: &Window) {
+    if !window.is_visible() {
+        let client = window.imp().client();
+        if !window.imp().standalone.get() {
+            client.toggle_notification_center();
+        }
+        client.get_all();
+    }
+    window.present();
+}
+
+fn close_window(window: &Window) {
+    // hiding loses focus, which closes again
+    if !window.is_visible() {
+        return;
+    }
+    let client = window.imp().client();
+    if !window.imp().standalone.get() {
+        client.toggle_notification_center();
+    }
+    // timed do not disturb can only end while OxiDash runs
+    if window.imp().resident.get() || dnd::pending() {
+        window.hide();
+        return;
+    }
+    // make sure the server got everything before the process is gone
+    client.sync();
+    window.close();
+}
+
+// Holds the application until timed do not disturb ended, then closes a
+// window that is only still around for that.
+fn dnd_ended(window: &Window) -> impl FnOnce() + 'static {
+    let hold = window.application().map(|app| app.hold());
+    let window = window.downgrade();
+    move || {
+        drop(hold);
+        let Some(window) = window.upgrade() else {
+            return;
+        };
+        if !window.is_visible() && !window.imp().resident.get() && !dnd::pending() {
+            window.imp().client().sync();
+            window.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Urgency, UrgencyEncoding};
+
+    #[test]
+    fn urgencies_decode_per_encoding() {
+        let table = [
+            (UrgencyEncoding::Spec, 0, Urgency::Low),
+            (UrgencyEncoding::Spec, 1, Urgency::Normal),
+            (UrgencyEncoding::Spec, 2, Urgency::Urgent),
+            (UrgencyEncoding::Spec, 3, Urgency::Normal),
+            (UrgencyEncoding::Spec, -1, Urgency::Normal),
+            (UrgencyEncoding::Spec, 255, Urgency::Normal),
+            (UrgencyEncoding::Oxinoti, 0, Urgency::Normal),
+            (UrgencyEncoding::Oxinoti, 1, Urgency::Low),
+            (UrgencyEncoding::Oxinoti, 2, Urgency::Normal),
+            (UrgencyEncoding::Oxinoti, 3, Urgency::Urgent),
+            (UrgencyEncoding::Oxinoti, 4, Urgency::Normal),
+            (UrgencyEncoding::Oxinoti, -1, Urgency::Normal),
+        ];
+        for (encoding, value, urgency) in table {
+            assert_eq!(
+                Urgency::from_i32(value, encoding),
+                urgency,
+                "{} as {:?}",
+                value,
+                encoding
+            );
+        }
+    }
+
+    #[test]
+    fn urgencies_round_trip() {
+        for encoding in [UrgencyEncoding::Spec, UrgencyEncoding::Oxinoti] {
+            for urgency in [Urgency::Low, Urgency::Normal, Urgency::Urgent] {
+                let value = urgency.to_i32(encoding);
+                assert_eq!(Urgency::from_i32(value, encoding), urgency);
+            }
+        }
+        assert_eq!(Urgency::Urgent.to_i32(UrgencyEncoding::Spec), 2);
+        assert_eq!(Urgency::Urgent.to_i32(UrgencyEncoding::Oxinoti), 3);
+    }
+
+    #[test]
+    fn oxinoti_is_the_default_encoding() {
+        assert_eq!(UrgencyEncoding::default(), UrgencyEncoding::Oxinoti);
+        assert_eq!("spec".parse(), Ok(UrgencyEncoding::Spec));
+        assert!("critical".parse::<UrgencyEncoding>().is_err());
     }
-    false
 }
@@ -3,8 +3,16 @@ use std::cell::{RefCell, Cell};
 use gtk::subclass::prelude::*;
 use gtk::{glib, Image, Label, ProgressBar};
 
+use crate::Urgency;
+
 #[derive(Default)]
 pub struct NotiBox {
+    pub id: Cell<u32>,
+    pub group: RefCell<String>,
+    pub app_name: RefCell<String>,
+    pub app_icon: RefCell<String>,
+    pub pinned: Cell<bool>,
+    pub urgency: RefCell<Urgency>,
     pub basebox: RefCell<gtk::Box>,
     pub textbox: RefCell<gtk::Box>,
     pub picbuttonbox: RefCell<gtk::Box>,
@@ -20,6 +28,12 @@ pub struct NotiBox {
     pub has_inline_reply: Cell<bool>,
     pub body_image: RefCell<Image>,
     pub has_body_image: Cell<bool>,
+    pub action_box: RefCell<gtk::Box>,
+    pub has_default_action: Cell<bool>,
+    pub resident: Cell<bool>,
+    pub category_classes: RefCell<Vec<String>>,
+    pub time: RefCell<Label>,
+    pub received: Cell<i64>,
 }
 
 #[glib::object_subclass]
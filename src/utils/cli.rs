@@ -0,0 +1,345 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use gtk::glib::ExitCode;
+
+use super::client::{failed, ClientError, Server};
+use super::config::Config;
+use super::dnd;
+use super::export::{self, ListFormat};
+use super::position::Position;
+use super::sort::SortMode;
+use crate::UrgencyEncoding;
+
+pub const USAGE: &str = "usage: oxidash [options] [command]
+
+commands:
+    toggle: open the dashboard, or close it when it is open. The default.
+    open: open the dashboard.
+    close: close the dashboard.
+    clear: remove all notifications.
+    dnd on|off|toggle: change do not disturb.
+    list: print the current notifications without opening the dashboard.
+    dismiss <id>: remove one notification.
+
+options:
+    --css <path>: use a specific path to load a css style sheet.
+    --config <path>: use a specific config file instead of config.json in the config directory.
+    --position <top-left|top-right|bottom-left|bottom-right|left|right>: where the dashboard opens, left and right are full height.
+    --sort <newest|oldest|urgency|app>: how notifications are sorted.
+    --urgency-encoding <spec|oxinoti>: read urgencies as spec (0-2) or oxinoti (1-3, default) values.
+    --standalone: run as the notification server instead of next to oxinoti.
+    --daemon: keep running with the window hidden instead of quitting on close.
+    --json: print the list as a json array instead of tab separated text.
+    --ndjson: print the list as one json object per line.
+    --image-data: include image data as base64 in the json of list.
+    --verbose: print what is sent over dbus.
+    --help: show this message.
+";
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+pub fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum DndChange {
+    On,
+    Off,
+    Toggle,
+}
+
+impl FromStr for DndChange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "on" => Ok(DndChange::On),
+            "off" => Ok(DndChange::Off),
+            "toggle" => Ok(DndChange::Toggle),
+            _ => Err(format!(
+                "invalid dnd change {}, only on, off or toggle allowed",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub enum Command {
+    #[default]
+    Toggle,
+    Open,
+    Close,
+    Clear,
+    Dnd(DndChange),
+    List,
+    Dismiss(u32),
+    Help,
+}
+
+impl Command {
+    // the app action a running dashboard handles for this command
+    pub fn action(&self) -> Option<&'static str> {
+        match self {
+            Command::Toggle => Some("toggle"),
+            Command::Open => Some("open"),
+            Command::Close => Some("close"),
+            _ => None,
+        }
+    }
+    pub fn is_headless(&self) -> bool {
+        matches!(
+            self,
+            Command::Clear | Command::Dnd(_) | Command::List | Command::Dismiss(_)
+        )
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Cli {
+    pub command: Command,
+    pub css: Option<String>,
+    pub config: Option<PathBuf>,
+    // these override the config file
+    pub position: Option<Position>,
+    pub sort_mode: Option<SortMode>,
+    pub urgency_encoding: Option<UrgencyEncoding>,
+    pub standalone: bool,
+    pub daemon: bool,
+    pub verbose: bool,
+    pub format: ListFormat,
+    pub image_data: bool,
+}
+
+impl Cli {
+    // the window outlives closing it
+    pub fn resident(&self) -> bool {
+        self.daemon || self.standalone
+    }
+    pub fn override_config(&self, config: &mut Config) {
+        if let Some(position) = self.position {
+            config.layout.position = position;
+        }
+        if let Some(sort_mode) = self.sort_mode {
+            config.behaviour.sort = sort_mode;
+        }
+        if let Some(encoding) = self.urgency_encoding {
+            config.behaviour.urgency_encoding = encoding;
+        }
+    }
+}
+
+fn value<T: FromStr>(option: &str, args: &mut impl Iterator<Item = String>) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    args.next()
+        .ok_or_else(|| format!("{} needs a value", option))?
+        .parse()
+        .map_err(|error: T::Err| error.to_string())
+}
+
+pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Cli, String> {
+    let mut cli = Cli::default();
+    let mut command = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--css" => cli.css = Some(value(&arg, &mut args)?),
+            "--config" => cli.config = Some(value(&arg, &mut args)?),
+            "--position" => cli.position = Some(value(&arg, &mut args)?),
+            "--sort" => cli.sort_mode = Some(value(&arg, &mut args)?),
+            "--urgency-encoding" => cli.urgency_encoding = Some(value(&arg, &mut args)?),
+            "--standalone" => cli.standalone = true,
+            "--daemon" => cli.daemon = true,
+            "--verbose" => cli.verbose = true,
+            "--json" => cli.format = ListFormat::Json,
+            "--ndjson" => cli.format = ListFormat::Ndjson,
+            "--image-data" => cli.image_data = true,
+            _ => {
+                if command.replace(parse_command(&arg, &mut args)?).is_some() {
+                    return Err("only one command at a time".into());
+                }
+            }
+        }
+    }
+    cli.command = command.unwrap_or_default();
+    Ok(cli)
+}
+
+fn parse_command(arg: &str, args: &mut impl Iterator<Item = String>) -> Result<Command, String> {
+    match arg {
+        "--help" | "-h" | "help" => Ok(Command::Help),
+        "toggle" => Ok(Command::Toggle),
+        "open" => Ok(Command::Open),
+        "close" => Ok(Command::Close),
+        "clear" => Ok(Command::Clear),
+        "dnd" => Ok(Command::Dnd(value(arg, args)?)),
+        "list" => Ok(Command::List),
+        "dismiss" => value(arg, args)
+            .map(Command::Dismiss)
+            .map_err(|_| "dismiss needs a notification id".into()),
+        _ => Err(format!("unknown argument {}", arg)),
+    }
+}
+
+// Commands that only talk to the notification server, no window involved.
+pub fn run_headless(cli: &Cli, encoding: UrgencyEncoding) -> Option<ExitCode> {
+    if !cli.command.is_headless() {
+        return None;
+    }
+    if verbose() {
+        eprintln!("sending {:?}", cli.command);
+    }
+    let result = connect().and_then(|server| run(&server, cli, encoding));
+    Some(match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    })
+}
+
+fn run(server: &Server, cli: &Cli, encoding: UrgencyEncoding) -> Result<(), ClientError> {
+    match cli.command {
+        Command::Clear => server.clear_all().map_err(failed("clear notifications")),
+        Command::Dnd(change) => {
+            // set by hand, so a running timer must not turn it off again
+            dnd::set_deadline(None);
+            match change {
+                DndChange::On => server.set_dnd(true),
+                DndChange::Off => server.set_dnd(false),
+                DndChange::Toggle => server.toggle_dnd(),
+            }
+            .map_err(failed("change do not disturb"))
+        }
+        Command::List => {
+            let notifications = server
+                .get_all(encoding)
+                .map_err(failed("get notifications"))?;
+            print!(
+                "{}",
+                export::write(&notifications, cli.format, cli.image_data)
+            );
+            Ok(())
+        }
+        Command::Dismiss(id) => server.dismiss(id).map_err(failed("dismiss notification")),
+        _ => Ok(()),
+    }
+}
+
+fn connect() -> Result<Server, ClientError> {
+    Server::connect().map_err(failed("connect to the session bus"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Cli, String> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn command(args: &[&str]) -> Result<Command, String> {
+        parse_args(args).map(|cli| cli.command)
+    }
+
+    #[test]
+    fn help_is_a_command() {
+        for help in ["--help", "-h", "help"] {
+            assert_eq!(command(&[help]), Ok(Command::Help));
+            assert_eq!(command(&["--verbose", help]), Ok(Command::Help));
+        }
+    }
+
+    #[test]
+    fn toggle_is_the_default() {
+        assert_eq!(command(&[]), Ok(Command::Toggle));
+        assert_eq!(command(&["--daemon"]), Ok(Command::Toggle));
+    }
+
+    #[test]
+    fn dnd_needs_a_change() {
+        let cases = [
+            ("on", DndChange::On),
+            ("off", DndChange::Off),
+            ("toggle", DndChange::Toggle),
+        ];
+        for (change, expected) in cases {
+            assert_eq!(command(&["dnd", change]), Ok(Command::Dnd(expected)));
+        }
+        assert_eq!(
+            command(&["dnd", "maybe"]),
+            Err("invalid dnd change maybe, only on, off or toggle allowed".into())
+        );
+        assert_eq!(command(&["dnd"]), Err("dnd needs a value".into()));
+    }
+
+    #[test]
+    fn dismiss_needs_an_id() {
+        assert_eq!(command(&["dismiss", "42"]), Ok(Command::Dismiss(42)));
+        for args in [&["dismiss", "abc"][..], &["dismiss", "-1"], &["dismiss"]] {
+            assert_eq!(
+                command(args),
+                Err("dismiss needs a notification id".into()),
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn only_one_command_at_a_time() {
+        for args in [
+            &["open", "close"][..],
+            &["list", "--help"],
+            &["dnd", "on", "clear"],
+        ] {
+            assert_eq!(
+                command(args),
+                Err("only one command at a time".into()),
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_arguments_are_rejected() {
+        assert_eq!(
+            command(&["--frobnicate"]),
+            Err("unknown argument --frobnicate".into())
+        );
+        assert_eq!(command(&["lsit"]), Err("unknown argument lsit".into()));
+        assert_eq!(command(&["--css"]), Err("--css needs a value".into()));
+    }
+
+    #[test]
+    fn options_are_read() {
+        let cli = parse_args(&[
+            "list",
+            "--json",
+            "--image-data",
+            "--config",
+            "/tmp/config.json",
+            "--urgency-encoding",
+            "spec",
+            "--standalone",
+        ])
+        .unwrap();
+        assert_eq!(cli.command, Command::List);
+        assert_eq!(cli.format, ListFormat::Json);
+        assert!(cli.image_data);
+        assert_eq!(cli.config, Some(PathBuf::from("/tmp/config.json")));
+        assert_eq!(cli.urgency_encoding, Some(UrgencyEncoding::Spec));
+        assert!(cli.resident());
+    }
+}
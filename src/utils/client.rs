@@ -0,0 +1,279 @@
+use std::fmt::Display;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::{Connection, Proxy};
+use gtk::glib::Sender;
+
+use super::cli::verbose;
+use super::dnd;
+use super::wire::RawNotification;
+use crate::{Notification, UrgencyEncoding};
+
+const SERVER_NAME: &str = "org.freedesktop.Notifications";
+const SERVER_PATH: &str = "/org/freedesktop/Notifications";
+const TIMEOUT: Duration = Duration::from_millis(1000);
+
+// Blocking calls to the notification server over one session connection.
+pub struct Server {
+    conn: Connection,
+}
+
+impl Server {
+    pub fn connect() -> Result<Server, dbus::Error> {
+        Ok(Server {
+            conn: Connection::new_session()?,
+        })
+    }
+    fn proxy(&self) -> Proxy<'_, &Connection> {
+        self.conn.with_proxy(SERVER_NAME, SERVER_PATH, TIMEOUT)
+    }
+    // RemoveNotification tells the server the user dismissed it, CloseNotification
+    // is what apps call and stays the fallback for servers without it
+    pub fn dismiss(&self, id: u32) -> Result<(), dbus::Error> {
+        let removed = self
+            .proxy()
+            .method_call(SERVER_NAME, "RemoveNotification", (id,));
+        match removed {
+            Err(error) if error.name() == Some("org.freedesktop.DBus.Error.UnknownMethod") => self
+                .proxy()
+                .method_call(SERVER_NAME, "CloseNotification", (id,)),
+            removed => removed,
+        }
+    }
+    pub fn clear_all(&self) -> Result<(), dbus::Error> {
+        self.proxy()
+            .method_call(SERVER_NAME, "RemoveAllNotifications", ())
+    }
+    pub fn toggle_dnd(&self) -> Result<(), dbus::Error> {
+        self.proxy().method_call(SERVER_NAME, "DoNotDisturb", ())
+    }
+    pub fn do_not_disturb(&self) -> Result<bool, dbus::Error> {
+        self.proxy().get(SERVER_NAME, "DoNotDisturb")
+    }
+    // the server only knows how to toggle, so check where it is first
+    pub fn set_dnd(&self, enabled: bool) -> Result<(), dbus::Error> {
+        let current = self.do_not_disturb().map_err(|error| {
+            if !missing_property(&error) {
+                return error;
+            }
+            dbus::Error::new_custom(
+                "org.freedesktop.DBus.Error.NotSupported",
+                "the server does not tell whether do not disturb is on, it can only be toggled",
+            )
+        })?;
+        if current != enabled {
+            self.toggle_dnd()?;
+        }
+        Ok(())
+    }
+    pub fn inline_reply(&self, id: u32, text: &str) -> Result<(), dbus::Error> {
+        self.proxy()
+            .method_call(SERVER_NAME, "InlineReply", (id, text))
+    }
+    pub fn invoke_action(&self, id: u32, key: &str) -> Result<(), dbus::Error> {
+        self.proxy()
+            .method_call(SERVER_NAME, "InvokeAction", (id, key))
+    }
+    pub fn get_all(&self, encoding: UrgencyEncoding) -> Result<Vec<Notification>, dbus::Error> {
+        let (notifications,): (Vec<RawNotification>,) =
+            self.proxy()
+                .method_call(SERVER_NAME, "GetAllNotifications", ())?;
+        Ok(notifications
+            .into_iter()
+            .map(|notification| notification.decode(encoding))
+            .collect())
+    }
+    pub fn toggle_notification_center(&self) -> Result<bool, dbus::Error> {
+        let (open,): (bool,) =
+            self.proxy()
+                .method_call(SERVER_NAME, "ToggleNotificationCenter", ())?;
+        Ok(open)
+    }
+}
+
+// servers without a readable DoNotDisturb property can only toggle it
+fn missing_property(error: &dbus::Error) -> bool {
+    matches!(
+        error.name(),
+        Some(
+            "org.freedesktop.DBus.Error.UnknownProperty"
+                | "org.freedesktop.DBus.Error.UnknownInterface"
+                | "org.freedesktop.DBus.Error.InvalidArgs"
+        )
+    )
+}
+
+#[derive(Debug)]
+enum Request {
+    Dismiss(u32),
+    ClearAll,
+    ToggleDnd,
+    SetDnd(bool),
+    StartDnd(i64),
+    GetDnd,
+    InlineReply(u32, String),
+    InvokeAction(u32, String),
+    GetAll,
+    ToggleNotificationCenter,
+    Sync(mpsc::Sender<()>),
+}
+
+pub struct ClientError {
+    pub what: &'static str,
+    pub error: dbus::Error,
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Could not {}: {}",
+            self.what,
+            self.error.message().unwrap_or("unknown error")
+        )
+    }
+}
+
+pub enum Reply {
+    Notifications(Vec<Notification>),
+    // None when the server does not say whether do not disturb is on
+    DoNotDisturb(Option<bool>),
+    Failed(ClientError),
+}
+
+pub fn failed(what: &'static str) -> impl FnOnce(dbus::Error) -> ClientError {
+    move |error| ClientError { what, error }
+}
+
+// Handle to a worker thread that owns the server connection. Requests are
+// sent in order and never block the caller, results come back as replies
+// on the glib main loop.
+#[derive(Clone)]
+pub struct Client {
+    requests: mpsc::Sender<Request>,
+}
+
+impl Client {
+    pub fn new(replies: Sender<Reply>, encoding: UrgencyEncoding) -> Client {
+        let (requests, receiver) = mpsc::channel();
+        thread::spawn(move || serve(receiver, replies, encoding));
+        Client { requests }
+    }
+    fn send(&self, request: Request) {
+        // the worker only stops when every client is gone
+        let _ = self.requests.send(request);
+    }
+    pub fn dismiss(&self, id: u32) {
+        self.send(Request::Dismiss(id));
+    }
+    pub fn clear_all(&self) {
+        self.send(Request::ClearAll);
+    }
+    pub fn toggle_dnd(&self) {
+        self.send(Request::ToggleDnd);
+    }
+    pub fn set_dnd(&self, enabled: bool) {
+        self.send(Request::SetDnd(enabled));
+    }
+    // the end is only stored once the server turned do not disturb on
+    pub fn start_dnd(&self, until: i64) {
+        self.send(Request::StartDnd(until));
+    }
+    pub fn get_dnd(&self) {
+        self.send(Request::GetDnd);
+    }
+    pub fn inline_reply(&self, id: u32, text: String) {
+        self.send(Request::InlineReply(id, text));
+    }
+    pub fn invoke_action(&self, id: u32, key: String) {
+        self.send(Request::InvokeAction(id, key));
+    }
+    pub fn get_all(&self) {
+        self.send(Request::GetAll);
+    }
+    pub fn toggle_notification_center(&self) {
+        self.send(Request::ToggleNotificationCenter);
+    }
+    // Waits until everything requested so far went out, used before quitting.
+    pub fn sync(&self) {
+        let (done, wait) = mpsc::channel();
+        self.send(Request::Sync(done));
+        let _ = wait.recv_timeout(TIMEOUT * 2);
+    }
+}
+
+fn serve(receiver: mpsc::Receiver<Request>, replies: Sender<Reply>, encoding: UrgencyEncoding) {
+    let mut server = None;
+    for request in receiver {
+        if verbose() {
+            eprintln!("sending {:?}", request);
+        }
+        if let Request::Sync(done) = request {
+            let _ = done.send(());
+            continue;
+        }
+        if server.is_none() {
+            match Server::connect() {
+                Ok(connected) => server = Some(connected),
+                Err(error) => {
+                    let error = failed("connect to the session bus")(error);
+                    let _ = replies.send(Reply::Failed(error));
+                    continue;
+                }
+            }
+        }
+        let server = server.as_ref().unwrap();
+        let result = match request {
+            Request::Dismiss(id) => server.dismiss(id).map_err(failed("dismiss notification")),
+            Request::ClearAll => server.clear_all().map_err(failed("clear notifications")),
+            Request::ToggleDnd => server
+                .toggle_dnd()
+                .map(|_| send_dnd(server, &replies))
+                .map_err(failed("toggle do not disturb")),
+            Request::SetDnd(enabled) => server
+                .set_dnd(enabled)
+                .map(|_| send_dnd(server, &replies))
+                .map_err(failed("set do not disturb")),
+            Request::StartDnd(until) => server
+                .set_dnd(true)
+                .map(|_| {
+                    dnd::set_deadline(Some(until));
+                    send_dnd(server, &replies);
+                })
+                .map_err(failed("start do not disturb")),
+            Request::GetDnd => {
+                send_dnd(server, &replies);
+                Ok(())
+            }
+            Request::InlineReply(id, text) => {
+                server.inline_reply(id, &text).map_err(failed("send reply"))
+            }
+            Request::InvokeAction(id, key) => server
+                .invoke_action(id, &key)
+                .map_err(failed("invoke action")),
+            Request::GetAll => server
+                .get_all(encoding)
+                .map(|notifications| {
+                    let _ = replies.send(Reply::Notifications(notifications));
+                })
+                .map_err(failed("get notifications")),
+            Request::ToggleNotificationCenter => server
+                .toggle_notification_center()
+                .map(|_| ())
+                .map_err(failed("toggle the notification center")),
+            Request::Sync(_) => Ok(()),
+        };
+        if let Err(error) = result {
+            let _ = replies.send(Reply::Failed(error));
+        }
+    }
+}
+
+// servers without the property just get a button that can't show the state
+fn send_dnd(server: &Server, replies: &Sender<Reply>) {
+    let enabled = server.do_not_disturb().ok();
+    let _ = replies.send(Reply::DoNotDisturb(enabled));
+}
@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use directories_next as dirs;
+use gtk::gdk::Key;
+use gtk4_layer_shell::Edge;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+
+use super::position::Position;
+use super::sort::SortMode;
+use crate::UrgencyEncoding;
+
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layer {
+    Background,
+    Bottom,
+    Top,
+    #[default]
+    Overlay,
+}
+
+impl From<Layer> for gtk4_layer_shell::Layer {
+    fn from(layer: Layer) -> Self {
+        match layer {
+            Layer::Background => gtk4_layer_shell::Layer::Background,
+            Layer::Bottom => gtk4_layer_shell::Layer::Bottom,
+            Layer::Top => gtk4_layer_shell::Layer::Top,
+            Layer::Overlay => gtk4_layer_shell::Layer::Overlay,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyboardMode {
+    None,
+    #[default]
+    Exclusive,
+    OnDemand,
+}
+
+impl From<KeyboardMode> for gtk4_layer_shell::KeyboardMode {
+    fn from(mode: KeyboardMode) -> Self {
+        match mode {
+            KeyboardMode::None => gtk4_layer_shell::KeyboardMode::None,
+            KeyboardMode::Exclusive => gtk4_layer_shell::KeyboardMode::Exclusive,
+            KeyboardMode::OnDemand => gtk4_layer_shell::KeyboardMode::OnDemand,
+        }
+    }
+}
+
+// Without a name the compositor picks the output, usually the focused one.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub enum Monitor {
+    #[default]
+    Focused,
+    // a connector like DP-1
+    Named(String),
+}
+
+impl FromStr for Monitor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "focused" => Ok(Monitor::Focused),
+            "" => Err("invalid monitor, only focused or a connector name like DP-1 allowed".into()),
+            _ => Ok(Monitor::Named(s.into())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Margins {
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub left: i32,
+}
+
+impl Margins {
+    pub fn edges(&self) -> [(Edge, i32); 4] {
+        [
+            (Edge::Top, self.top),
+            (Edge::Right, self.right),
+            (Edge::Bottom, self.bottom),
+            (Edge::Left, self.left),
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyAction {
+    Close,
+    ClearAll,
+    ToggleDnd,
+    FocusNext,
+    FocusPrevious,
+    DismissFocused,
+    ActivateDefaultAction,
+    // collapses or expands the group of the focused notification
+    Expand,
+    StartInlineReply,
+}
+
+// used for keys that keybindings leaves unbound when vim_keys is on
+const VIM_KEYS: [(&str, KeyAction); 4] = [
+    ("j", KeyAction::FocusNext),
+    ("k", KeyAction::FocusPrevious),
+    ("d", KeyAction::DismissFocused),
+    ("Return", KeyAction::ActivateDefaultAction),
+];
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Layout {
+    pub width: i32,
+    pub max_height: i32,
+    pub image_size: i32,
+    #[serde(deserialize_with = "parsed")]
+    pub position: Position,
+    pub margins: Margins,
+    pub layer: Layer,
+    pub keyboard_mode: KeyboardMode,
+    // keep other windows out of the space the dashboard takes
+    pub exclusive_zone: bool,
+    // for compositor rules
+    pub namespace: String,
+    #[serde(deserialize_with = "parsed")]
+    pub monitor: Monitor,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            width: 300,
+            max_height: 1000,
+            image_size: 100,
+            position: Position::default(),
+            margins: Margins::default(),
+            layer: Layer::default(),
+            keyboard_mode: KeyboardMode::default(),
+            exclusive_zone: true,
+            namespace: "gtk-layer-shell".into(),
+            monitor: Monitor::default(),
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Behaviour {
+    #[serde(deserialize_with = "parsed")]
+    pub sort: SortMode,
+    #[serde(deserialize_with = "parsed")]
+    pub urgency_encoding: UrgencyEncoding,
+}
+
+// Everything besides the looks, which stay in style.css. Missing keys keep
+// their defaults, unknown ones are an error so typos don't go unnoticed.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub layout: Layout,
+    pub behaviour: Behaviour,
+    // gdk key names like Escape, 1 or j
+    pub keybindings: BTreeMap<String, KeyAction>,
+    pub vim_keys: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            layout: Layout::default(),
+            behaviour: Behaviour::default(),
+            keybindings: BTreeMap::from([
+                ("Escape".into(), KeyAction::Close),
+                ("1".into(), KeyAction::ToggleDnd),
+                ("2".into(), KeyAction::Close),
+                ("3".into(), KeyAction::ClearAll),
+            ]),
+            vim_keys: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn binding(&self, key: Key) -> Option<KeyAction> {
+        let vim_keys = VIM_KEYS.iter().filter(|_| self.vim_keys);
+        self.keybindings
+            .iter()
+            .map(|(name, action)| (name.as_str(), action))
+            .chain(vim_keys.map(|(name, action)| (*name, action)))
+            .find(|(name, _)| Key::from_name(name) == Some(key))
+            .map(|(_, action)| *action)
+    }
+    fn validate(&self) -> Result<(), String> {
+        let positive = [
+            ("layout.width", self.layout.width),
+            ("layout.max_height", self.layout.max_height),
+            ("layout.image_size", self.layout.image_size),
+        ];
+        for (name, value) in positive {
+            if value <= 0 {
+                return Err(format!("{} has to be positive, got {}", name, value));
+            }
+        }
+        if self.layout.namespace.is_empty() {
+            return Err("layout.namespace can not be empty".into());
+        }
+        for name in self.keybindings.keys() {
+            if Key::from_name(name.as_str()).is_none() {
+                return Err(format!("keybindings has an unknown key {}", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parsed<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(de::Error::custom)
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    dirs::ProjectDirs::from("com", "dashie", "oxidash")
+        .map(|dirs| dirs.config_dir().join("config.json"))
+}
+
+// Only a config file that was asked for has to exist, otherwise the defaults are fine.
+pub fn load(path: Option<&Path>) -> Result<Config, String> {
+    let (path, required) = match path {
+        Some(path) => (path.to_path_buf(), true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok(Config::default()),
+        },
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) if !required && error.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Config::default())
+        }
+        Err(error) => return Err(format!("Could not read {}: {}", path.display(), error)),
+    };
+    let config: Config = serde_json::from_str(&contents)
+        .map_err(|error| format!("Invalid config {}: {}", path.display(), error))?;
+    config
+        .validate()
+        .map_err(|error| format!("Invalid config {}: {}", path.display(), error))?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn load_json(name: &str, json: &str) -> Result<Config, String> {
+        let path = env::temp_dir().join(format!("oxidash-{}-{}.json", process::id(), name));
+        fs::write(&path, json).unwrap();
+        let config = load(Some(&path));
+        let _ = fs::remove_file(&path);
+        config
+    }
+
+    fn rejection(name: &str, json: &str) -> String {
+        load_json(name, json).expect_err("the config should be rejected")
+    }
+
+    #[test]
+    fn missing_keys_keep_their_defaults() {
+        let config = load_json("empty", "{}").unwrap();
+        let defaults = Config::default();
+        assert_eq!(config.layout.width, defaults.layout.width);
+        assert_eq!(config.keybindings, defaults.keybindings);
+        assert!(!config.vim_keys);
+
+        let config = load_json("partial", r#"{ "layout": { "width": 400 } }"#).unwrap();
+        assert_eq!(config.layout.width, 400);
+        assert_eq!(config.layout.max_height, 1000);
+        assert_eq!(config.layout.image_size, 100);
+        assert_eq!(config.layout.namespace, "gtk-layer-shell");
+        assert!(config.layout.exclusive_zone);
+        assert_eq!(config.layout.monitor, Monitor::Focused);
+        assert_eq!(config.layout.layer, Layer::Overlay);
+        assert_eq!(config.behaviour.urgency_encoding, UrgencyEncoding::Oxinoti);
+        assert_eq!(config.keybindings, defaults.keybindings);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let cases = [
+            ("top-level", r#"{ "layuot": {} }"#, "unknown field `layuot`"),
+            (
+                "nested",
+                r#"{ "layout": { "widht": 400 } }"#,
+                "unknown field `widht`",
+            ),
+            (
+                "margins",
+                r#"{ "layout": { "margins": { "up": 1 } } }"#,
+                "unknown field `up`",
+            ),
+        ];
+        for (name, json, expected) in cases {
+            let error = rejection(name, json);
+            assert!(error.starts_with("Invalid config "), "{}", error);
+            assert!(error.contains(expected), "{}", error);
+        }
+    }
+
+    #[test]
+    fn sizes_have_to_be_positive() {
+        let cases = [
+            (
+                "width",
+                r#"{ "layout": { "width": 0 } }"#,
+                "layout.width has to be positive, got 0",
+            ),
+            (
+                "max-height",
+                r#"{ "layout": { "max_height": -5 } }"#,
+                "layout.max_height has to be positive, got -5",
+            ),
+            (
+                "image-size",
+                r#"{ "layout": { "image_size": 0 } }"#,
+                "layout.image_size has to be positive, got 0",
+            ),
+        ];
+        for (name, json, expected) in cases {
+            let error = rejection(name, json);
+            assert!(error.ends_with(expected), "{}", error);
+        }
+    }
+
+    #[test]
+    fn keybindings_need_known_keys_and_actions() {
+        let error = rejection("key", r#"{ "keybindings": { "NoSuchKey": "close" } }"#);
+        assert!(
+            error.ends_with("keybindings has an unknown key NoSuchKey"),
+            "{}",
+            error
+        );
+        let error = rejection("action", r#"{ "keybindings": { "1": "fly" } }"#);
+        assert!(error.contains("unknown variant `fly`"), "{}", error);
+    }
+
+    #[test]
+    fn values_are_checked() {
+        let error = rejection("namespace", r#"{ "layout": { "namespace": "" } }"#);
+        assert!(
+            error.ends_with("layout.namespace can not be empty"),
+            "{}",
+            error
+        );
+        let error = rejection("monitor", r#"{ "layout": { "monitor": "" } }"#);
+        assert!(error.contains("invalid monitor"), "{}", error);
+        let error = rejection("syntax", "{ \"layout\": ");
+        assert!(error.starts_with("Invalid config "), "{}", error);
+    }
+
+    #[test]
+    fn a_requested_file_has_to_exist() {
+        let path = env::temp_dir().join(format!("oxidash-{}-missing.json", process::id()));
+        let error = load(Some(&path)).expect_err("a missing file should be rejected");
+        assert!(error.starts_with("Could not read "), "{}", error);
+    }
+}
@@ -0,0 +1,283 @@
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::stdintf::org_freedesktop_dbus::{
+    PropertiesPropertiesChanged, RequestNameReply,
+};
+use dbus::blocking::Connection;
+use dbus::message::SignalArgs;
+use dbus::MethodErr;
+use dbus_crossroads::{Context, Crossroads};
+use gtk::glib::Sender;
+
+use super::listener::Event;
+use super::time;
+use super::wire::RawNotification;
+use crate::{ImageData, Notification, Urgency, UrgencyEncoding};
+
+const SERVER_NAME: &str = "org.freedesktop.Notifications";
+const SERVER_PATH: &str = "/org/freedesktop/Notifications";
+
+// reasons for NotificationClosed from the spec
+const CLOSED_DISMISSED: u32 = 2;
+const CLOSED_BY_CALL: u32 = 3;
+
+// Notify(app_name, replaces_id, app_icon, summary, body, actions, hints, expire_timeout)
+type NotifyArgs = (
+    String,
+    u32,
+    String,
+    String,
+    String,
+    Vec<String>,
+    PropMap,
+    i32,
+);
+
+#[derive(Default)]
+struct History {
+    notifications: Vec<Notification>,
+    last_id: u32,
+    do_not_disturb: bool,
+}
+
+impl History {
+    fn close(&mut self, id: u32) -> bool {
+        let len = self.notifications.len();
+        self.notifications
+            .retain(|notification| notification.replaces_id != id);
+        self.notifications.len() != len
+    }
+}
+
+// Claims org.freedesktop.Notifications, fails if another server already owns it.
+pub fn claim() -> Result<Connection, dbus::Error> {
+    let c = Connection::new_session()?;
+    match c.request_name(SERVER_NAME, false, false, true)? {
+        RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => Ok(c),
+        _ => Err(dbus::Error::new_custom(
+            "org.freedesktop.DBus.Error.AddressInUse",
+            "another notification server is already running",
+        )),
+    }
+}
+
+// A history only notification server. It implements the spec methods for
+// applications and the oxinoti extensions OxiDash itself calls, and feeds
+// every new notification into the same channel as NotificationCenter.Notify.
+// GetAllNotifications uses the same urgency encoding OxiDash reads with.
+pub fn serve(
+    c: Connection,
+    sender: Sender<Event>,
+    encoding: UrgencyEncoding,
+) -> Result<(), dbus::Error> {
+    let mut cr = Crossroads::new();
+    let token = cr.register(SERVER_NAME, |b| {
+        b.signal::<(u32, u32), _>("NotificationClosed", ("id", "reason"));
+        b.signal::<(u32, String), _>("ActionInvoked", ("id", "action_key"));
+        b.signal::<(u32, String), _>("NotificationReplied", ("id", "text"));
+        b.method(
+            "Notify",
+            (
+                "app_name",
+                "replaces_id",
+                "app_icon",
+                "summary",
+                "body",
+                "actions",
+                "hints",
+                "expire_timeout",
+            ),
+            ("id",),
+            move |_, history: &mut History, args: NotifyArgs| {
+                let mut notification = from_notify(args);
+                let id = notification.replaces_id;
+                if id == 0 || !history.close(id) {
+                    history.last_id = history.last_id.wrapping_add(1).max(1);
+                    notification.replaces_id = history.last_id;
+                }
+                let id = notification.replaces_id;
+                // transient notifications only pop up, they are not kept
+                if !notification.transient() {
+                    history.notifications.push(notification.clone());
+                }
+                sender
+                    .send(Event::Notify(notification))
+                    .map_err(|_| MethodErr::failed("dashboard is gone"))?;
+                Ok((id,))
+            },
+        );
+        b.method(
+            "CloseNotification",
+            ("id",),
+            (),
+            |ctx, history: &mut History, (id,): (u32,)| {
+                if history.close(id) {
+                    closed(ctx, id, CLOSED_BY_CALL);
+                }
+                Ok(())
+            },
+        );
+        // only called by the dashboard, when the user dismissed the notification
+        b.method(
+            "RemoveNotification",
+            ("id",),
+            (),
+            |ctx, history: &mut History, (id,): (u32,)| {
+                if history.close(id) {
+                    closed(ctx, id, CLOSED_DISMISSED);
+                }
+                Ok(())
+            },
+        );
+        b.method("GetCapabilities", (), ("capabilities",), |_, _, ()| {
+            let capabilities = [
+                "actions",
+                "body",
+                "body-hyperlinks",
+                "body-images",
+                "body-markup",
+                "icon-static",
+                "inline-reply",
+                "persistence",
+            ];
+            Ok((capabilities.map(String::from).to_vec(),))
+        });
+        b.method(
+            "GetServerInformation",
+            (),
+            ("name", "vendor", "version", "spec_version"),
+            |_, _, ()| {
+                Ok((
+                    "OxiDash".to_string(),
+                    "dashie".to_string(),
+                    env!("CARGO_PKG_VERSION").to_string(),
+                    "1.2".to_string(),
+                ))
+            },
+        );
+        b.method(
+            "GetAllNotifications",
+            (),
+            ("notifications",),
+            move |_, history: &mut History, ()| {
+                let notifications: Vec<RawNotification> = history
+                    .notifications
+                    .iter()
+                    .map(|notification| RawNotification::encode(notification.clone(), encoding))
+                    .collect();
+                Ok((notifications,))
+            },
+        );
+        b.method(
+            "RemoveAllNotifications",
+            (),
+            (),
+            |ctx, history: &mut History, ()| {
+                for notification in history.notifications.drain(..) {
+                    closed(ctx, notification.replaces_id, CLOSED_DISMISSED);
+                }
+                Ok(())
+            },
+        );
+        b.method(
+            "InvokeAction",
+            ("id", "action_key"),
+            (),
+            |ctx, _, (id, key): (u32, String)| {
+                ctx.push_msg(ctx.make_signal("ActionInvoked", (id, key)));
+                Ok(())
+            },
+        );
+        b.method(
+            "InlineReply",
+            ("id", "text"),
+            (),
+            |ctx, _, (id, text): (u32, String)| {
+                ctx.push_msg(ctx.make_signal("NotificationReplied", (id, text)));
+                Ok(())
+            },
+        );
+        b.property::<bool, _>("DoNotDisturb")
+            .get(|_, history: &mut History| Ok(history.do_not_disturb))
+            .emits_changed_true();
+        b.method("DoNotDisturb", (), (), |ctx, history: &mut History, ()| {
+            history.do_not_disturb = !history.do_not_disturb;
+            let mut changed_properties = PropMap::new();
+            changed_properties.insert(
+                "DoNotDisturb".to_string(),
+                Variant(Box::new(history.do_not_disturb)),
+            );
+            let changed = PropertiesPropertiesChanged {
+                interface_name: SERVER_NAME.to_string(),
+                changed_properties,
+                invalidated_properties: Vec::new(),
+            };
+            ctx.push_msg(changed.to_emit_message(ctx.path()));
+            Ok(())
+        });
+        // asked by oxidash on startup, the standalone window itself is toggled through the app actions
+        b.method("ToggleNotificationCenter", (), ("open",), |_, _, ()| {
+            Ok((true,))
+        });
+    });
+    cr.insert(SERVER_PATH, &[token], History::default());
+    cr.serve(&c)
+}
+
+fn closed(ctx: &mut Context, id: u32, reason: u32) {
+    ctx.push_msg(ctx.make_signal("NotificationClosed", (id, reason)));
+}
+
+fn from_notify(args: NotifyArgs) -> Notification {
+    let (app_name, replaces_id, app_icon, summary, body, actions, hints, expire_timeout) = args;
+    let hint = |names: &[&str]| names.iter().find_map(|name| hints.get(*name));
+    let urgency = hint(&["urgency"])
+        .and_then(|hint| hint.0.as_i64())
+        .map_or(Urgency::Normal, |value| {
+            Urgency::from_i32(value as i32, UrgencyEncoding::Spec)
+        });
+    let image_path = hint(&["image-path", "image_path"])
+        .and_then(|hint| hint.0.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let image_data = hint(&["image-data", "image_data", "icon_data"])
+        .and_then(|hint| image_data(&hint.0))
+        .unwrap_or_else(ImageData::empty);
+    Notification {
+        app_name,
+        replaces_id,
+        app_icon,
+        summary,
+        body,
+        actions,
+        expire_timeout,
+        urgency,
+        image_path,
+        // the value hint is picked up by current_progress
+        progress: -1,
+        image_data,
+        received: time::now(),
+        hints,
+    }
+}
+
+fn image_data(hint: &dyn RefArg) -> Option<ImageData> {
+    let mut fields = hint.as_iter()?;
+    let mut int = || fields.next().and_then(|field| field.as_i64());
+    let (width, height, rowstride) = (int()? as i32, int()? as i32, int()? as i32);
+    let has_alpha = int()? != 0;
+    let (bits_per_sample, channels) = (int()? as i32, int()? as i32);
+    let data = fields
+        .next()?
+        .as_iter()?
+        .map(|byte| byte.as_u64().map(|byte| byte as u8))
+        .collect::<Option<Vec<u8>>>()?;
+    Some(ImageData {
+        width,
+        height,
+        rowstride,
+        has_alpha,
+        bits_per_sample,
+        channels,
+        data,
+    })
+}
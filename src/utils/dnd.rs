@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::PathBuf;
+
+use directories_next as dirs;
+use gtk::glib;
+
+use super::client::Client;
+use super::time;
+
+const CHECK_INTERVAL: u32 = 30;
+
+// Timed do not disturb outlives the process, the end is kept in the cache
+// dir and checked again whenever OxiDash starts.
+fn deadline_path() -> Option<PathBuf> {
+    dirs::ProjectDirs::from("com", "dashie", "oxidash")
+        .map(|dirs| dirs.cache_dir().join("dnd_until"))
+}
+
+pub fn deadline() -> Option<i64> {
+    fs::read_to_string(deadline_path()?)
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+// whether a timed do not disturb still has to be turned off
+pub fn pending() -> bool {
+    deadline().map_or(false, |until| until > time::now())
+}
+
+pub fn set_deadline(until: Option<i64>) {
+    let Some(path) = deadline_path() else {
+        return;
+    };
+    let result = match until {
+        Some(until) => path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, until.to_string())),
+        None if path.exists() => fs::remove_file(&path),
+        None => Ok(()),
+    };
+    if let Err(error) = result {
+        eprintln!("Could not store do not disturb end: {}", error);
+    }
+}
+
+pub fn start(client: &Client, until: i64, done: impl FnOnce() + 'static) {
+    client.start_dnd(until);
+    schedule_end(client, until, done);
+}
+
+// Turns do not disturb off at the stored end, unless it was changed meanwhile,
+// then calls done. An end that already passed is handled right away without done.
+pub fn schedule_end(client: &Client, until: i64, done: impl FnOnce() + 'static) {
+    let end = |client: &Client| {
+        if deadline() == Some(until) {
+            set_deadline(None);
+            client.set_dnd(false);
+        }
+    };
+    if until <= time::now() {
+        end(client);
+        return;
+    }
+    let client = client.clone();
+    let mut done = Some(done);
+    // the monotonic clock of a single timeout stops while suspended, so check the wall clock
+    glib::timeout_add_seconds_local(CHECK_INTERVAL, move || {
+        if deadline() == Some(until) && time::now() < until {
+            return glib::Continue(true);
+        }
+        end(&client);
+        if let Some(done) = done.take() {
+            done();
+        }
+        glib::Continue(false)
+    });
+}
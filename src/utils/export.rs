@@ -0,0 +1,236 @@
+use serde::Serialize;
+
+use crate::{Action, Notification, Urgency};
+
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub enum ListFormat {
+    #[default]
+    Text,
+    Json,
+    // one JSON object per line
+    Ndjson,
+}
+
+#[derive(Serialize)]
+struct ExportedImage {
+    width: i32,
+    height: i32,
+    rowstride: i32,
+    has_alpha: bool,
+    bits_per_sample: i32,
+    channels: i32,
+    data: String,
+}
+
+#[derive(Serialize)]
+struct Exported<'a> {
+    id: u32,
+    app: &'a str,
+    summary: &'a str,
+    body: &'a str,
+    actions: Vec<Action>,
+    urgency: &'a Urgency,
+    progress: Option<i32>,
+    image_path: Option<&'a str>,
+    received: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image_data: Option<ExportedImage>,
+}
+
+impl<'a> Exported<'a> {
+    fn new(notification: &'a Notification, with_image: bool) -> Self {
+        let image = &notification.image_data;
+        let progress = notification.current_progress();
+        Exported {
+            id: notification.replaces_id,
+            app: &notification.app_name,
+            summary: &notification.summary,
+            body: &notification.body,
+            actions: notification.parsed_actions(),
+            urgency: &notification.urgency,
+            progress: (progress > -1).then_some(progress),
+            image_path: Some(notification.image_path.as_str()).filter(|path| !path.is_empty()),
+            received: notification.received,
+            image_data: (with_image && !image.data.is_empty()).then(|| ExportedImage {
+                width: image.width,
+                height: image.height,
+                rowstride: image.rowstride,
+                has_alpha: image.has_alpha,
+                bits_per_sample: image.bits_per_sample,
+                channels: image.channels,
+                data: base64(&image.data),
+            }),
+        }
+    }
+}
+
+pub fn write(notifications: &[Notification], format: ListFormat, with_images: bool) -> String {
+    let exported = |notification| Exported::new(notification, with_images);
+    match format {
+        ListFormat::Text => notifications
+            .iter()
+            .map(|notification| {
+                format!(
+                    "{}\t{}\t{}\n",
+                    notification.replaces_id, notification.app_name, notification.summary
+                )
+            })
+            .collect(),
+        ListFormat::Json => {
+            let all: Vec<Exported> = notifications.iter().map(exported).collect();
+            serde_json::to_string_pretty(&all).unwrap() + "\n"
+        }
+        ListFormat::Ndjson => notifications
+            .iter()
+            .map(|notification| serde_json::to_string(&exported(notification)).unwrap() + "\n")
+            .collect(),
+    }
+}
+
+// standard alphabet with padding, the only place OxiDash needs it
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let triple = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (triple >> (18 - 6 * index)) & 0x3f;
+                encoded.push(ALPHABET[sextet as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::utils::tests::notification;
+    use crate::ImageData;
+
+    const KEYS: [&str; 9] = [
+        "id",
+        "app",
+        "summary",
+        "body",
+        "actions",
+        "urgency",
+        "progress",
+        "image_path",
+        "received",
+    ];
+
+    fn keys(object: &Value) -> Vec<&str> {
+        let mut keys: Vec<&str> = object
+            .as_object()
+            .expect("every notification is an object")
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    fn expected_keys(with_image: bool) -> Vec<&'static str> {
+        let mut keys = KEYS.to_vec();
+        if with_image {
+            keys.push("image_data");
+        }
+        keys.sort_unstable();
+        keys
+    }
+
+    fn parse_json(output: &str) -> Vec<Value> {
+        match serde_json::from_str(output).unwrap() {
+            Value::Array(all) => all,
+            other => panic!("expected an array, got {}", other),
+        }
+    }
+
+    fn parse_ndjson(output: &str) -> Vec<Value> {
+        assert!(output.ends_with('\n'));
+        output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn base64_matches_rfc_4648() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (input, encoded) in vectors {
+            assert_eq!(base64(input.as_bytes()), encoded, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn json_and_ndjson_have_the_documented_keys() {
+        let notifications = [notification(1, "first"), notification(2, "second")];
+        for with_image in [false, true] {
+            let json = parse_json(&write(&notifications, ListFormat::Json, with_image));
+            let ndjson = parse_ndjson(&write(&notifications, ListFormat::Ndjson, with_image));
+            assert_eq!(json, ndjson);
+            assert_eq!(json.len(), 2);
+            for object in &json {
+                assert_eq!(keys(object), expected_keys(with_image));
+            }
+        }
+    }
+
+    #[test]
+    fn values_are_exported() {
+        let output = write(&[notification(7, "hello")], ListFormat::Ndjson, true);
+        let object = &parse_ndjson(&output)[0];
+        assert_eq!(object["id"], 7);
+        assert_eq!(object["app"], "mail");
+        assert_eq!(object["summary"], "hello");
+        assert_eq!(object["urgency"], "urgent");
+        assert_eq!(object["progress"], Value::Null);
+        assert_eq!(object["image_path"], Value::Null);
+        assert_eq!(object["actions"][0]["key"], "default");
+        assert_eq!(object["actions"][0]["label"], "Open");
+        assert_eq!(object["image_data"]["width"], 1);
+        assert_eq!(object["image_data"]["data"], "AQIDBA==");
+    }
+
+    #[test]
+    fn image_data_needs_the_flag_and_an_image() {
+        let without_image = Notification {
+            image_data: ImageData::empty(),
+            ..notification(2, "plain")
+        };
+        let notifications = [notification(1, "image"), without_image];
+        let output = write(&notifications, ListFormat::Ndjson, false);
+        for object in parse_ndjson(&output) {
+            assert!(object.get("image_data").is_none());
+        }
+        let output = write(&notifications, ListFormat::Ndjson, true);
+        let objects = parse_ndjson(&output);
+        assert!(objects[0].get("image_data").is_some());
+        assert!(objects[1].get("image_data").is_none());
+    }
+
+    #[test]
+    fn text_is_tab_separated() {
+        let output = write(&[notification(3, "hi")], ListFormat::Text, true);
+        assert_eq!(output, "3\tmail\thi\n");
+    }
+}
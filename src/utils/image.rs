@@ -0,0 +1,235 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+
+use crate::ImageData;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ImageDataError {
+    Empty,
+    InvalidSize(i32, i32),
+    UnsupportedChannels(i32),
+    AlphaMismatch(i32, bool),
+    UnsupportedBitsPerSample(i32),
+    RowstrideTooSmall(i32, usize),
+    Truncated(usize, usize),
+}
+
+impl Display for ImageDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageDataError::Empty => write!(f, "no image data"),
+            ImageDataError::InvalidSize(width, height) => {
+                write!(f, "invalid image size {}x{}", width, height)
+            }
+            ImageDataError::UnsupportedChannels(channels) => {
+                write!(f, "unsupported channel count {}, only 3 or 4 allowed", channels)
+            }
+            ImageDataError::AlphaMismatch(channels, has_alpha) => write!(
+                f,
+                "{} channels do not match has_alpha={}",
+                channels, has_alpha
+            ),
+            ImageDataError::UnsupportedBitsPerSample(bits) => {
+                write!(f, "unsupported bits per sample {}, only 8 or 16 allowed", bits)
+            }
+            ImageDataError::RowstrideTooSmall(rowstride, needed) => write!(
+                f,
+                "rowstride {} is smaller than a row of {} bytes",
+                rowstride, needed
+            ),
+            ImageDataError::Truncated(len, needed) => {
+                write!(f, "image data has {} bytes, expected {}", len, needed)
+            }
+        }
+    }
+}
+
+impl ImageData {
+    pub fn is_empty(&self) -> bool {
+        self.width == -1 && self.data.is_empty()
+    }
+
+    // Checks the layout the sender claimed against the actual buffer and
+    // returns the image as 8 bits per sample, which is all Pixbuf can take.
+    pub fn normalize(&self) -> Result<Cow<'_, ImageData>, ImageDataError> {
+        if self.is_empty() {
+            return Err(ImageDataError::Empty);
+        }
+        if self.width <= 0 || self.height <= 0 {
+            return Err(ImageDataError::InvalidSize(self.width, self.height));
+        }
+        if self.channels != 3 && self.channels != 4 {
+            return Err(ImageDataError::UnsupportedChannels(self.channels));
+        }
+        if self.has_alpha != (self.channels == 4) {
+            return Err(ImageDataError::AlphaMismatch(self.channels, self.has_alpha));
+        }
+        let bytes_per_sample = match self.bits_per_sample {
+            8 => 1,
+            16 => 2,
+            bits => return Err(ImageDataError::UnsupportedBitsPerSample(bits)),
+        };
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let row_len = width
+            .checked_mul(self.channels as usize * bytes_per_sample)
+            .ok_or(ImageDataError::InvalidSize(self.width, self.height))?;
+        if self.rowstride < 0 || (self.rowstride as usize) < row_len {
+            return Err(ImageDataError::RowstrideTooSmall(self.rowstride, row_len));
+        }
+        let rowstride = self.rowstride as usize;
+        // the last row does not need to be padded up to the rowstride
+        let needed = rowstride
+            .checked_mul(height - 1)
+            .and_then(|len| len.checked_add(row_len))
+            .ok_or(ImageDataError::InvalidSize(self.width, self.height))?;
+        if self.data.len() < needed {
+            return Err(ImageDataError::Truncated(self.data.len(), needed));
+        }
+        if bytes_per_sample == 1 {
+            return Ok(Cow::Borrowed(self));
+        }
+
+        let samples = width * self.channels as usize;
+        let mut data = Vec::with_capacity(samples * height);
+        for row in self.data.chunks(rowstride).take(height) {
+            for sample in row[..row_len].chunks_exact(2) {
+                let value = u16::from_ne_bytes([sample[0], sample[1]]);
+                data.push((value >> 8) as u8);
+            }
+        }
+        Ok(Cow::Owned(ImageData {
+            width: self.width,
+            height: self.height,
+            rowstride: samples as i32,
+            has_alpha: self.has_alpha,
+            bits_per_sample: 8,
+            channels: self.channels,
+            data,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 2x2 image, 3 channels of 8 bits with 2 bytes of padding per row
+    fn image() -> ImageData {
+        ImageData {
+            width: 2,
+            height: 2,
+            rowstride: 8,
+            has_alpha: false,
+            bits_per_sample: 8,
+            channels: 3,
+            data: vec![0; 16],
+        }
+    }
+
+    fn with(change: impl FnOnce(&mut ImageData)) -> ImageData {
+        let mut image = image();
+        change(&mut image);
+        image
+    }
+
+    #[test]
+    fn rejects_broken_layouts() {
+        let cases = [
+            (ImageData::empty(), ImageDataError::Empty),
+            (
+                with(|image| image.width = 0),
+                ImageDataError::InvalidSize(0, 2),
+            ),
+            (
+                with(|image| image.height = -3),
+                ImageDataError::InvalidSize(2, -3),
+            ),
+            (
+                with(|image| image.channels = 2),
+                ImageDataError::UnsupportedChannels(2),
+            ),
+            (
+                with(|image| image.channels = 5),
+                ImageDataError::UnsupportedChannels(5),
+            ),
+            (
+                with(|image| image.has_alpha = true),
+                ImageDataError::AlphaMismatch(3, true),
+            ),
+            (
+                with(|image| image.channels = 4),
+                ImageDataError::AlphaMismatch(4, false),
+            ),
+            (
+                with(|image| image.bits_per_sample = 4),
+                ImageDataError::UnsupportedBitsPerSample(4),
+            ),
+            (
+                with(|image| image.rowstride = 5),
+                ImageDataError::RowstrideTooSmall(5, 6),
+            ),
+            (
+                with(|image| image.rowstride = -1),
+                ImageDataError::RowstrideTooSmall(-1, 6),
+            ),
+            (
+                with(|image| image.data = vec![0; 13]),
+                ImageDataError::Truncated(13, 14),
+            ),
+        ];
+        for (image, error) in cases {
+            assert_eq!(image.normalize(), Err(error), "{:?}", image);
+        }
+    }
+
+    #[test]
+    fn accepts_an_unpadded_last_row() {
+        let image = with(|image| image.data = vec![0; 14]);
+        assert_eq!(image.normalize(), Ok(Cow::Borrowed(&image)));
+    }
+
+    #[test]
+    fn converts_16_bits_to_8() {
+        // a 2x2 RGBA image with 4 bytes of padding per row, samples in native byte order
+        let samples: [[u16; 8]; 2] = [
+            [
+                0x0000, 0x00ff, 0x0100, 0x7fff, 0x8000, 0xabcd, 0xff00, 0xffff,
+            ],
+            [
+                0x1234, 0x5678, 0x9abc, 0xdef0, 0x0f0f, 0xf0f0, 0x00ab, 0xcd00,
+            ],
+        ];
+        let mut data = Vec::new();
+        for (row, samples) in samples.iter().enumerate() {
+            for sample in samples {
+                data.extend_from_slice(&sample.to_ne_bytes());
+            }
+            if row == 0 {
+                data.extend_from_slice(&[0xee; 4]);
+            }
+        }
+        let image = ImageData {
+            width: 2,
+            height: 2,
+            rowstride: 20,
+            has_alpha: true,
+            bits_per_sample: 16,
+            channels: 4,
+            data,
+        };
+        let expected = ImageData {
+            width: 2,
+            height: 2,
+            rowstride: 8,
+            has_alpha: true,
+            bits_per_sample: 8,
+            channels: 4,
+            data: vec![
+                0x00, 0x00, 0x01, 0x7f, 0x80, 0xab, 0xff, 0xff, //
+                0x12, 0x56, 0x9a, 0xde, 0x0f, 0xf0, 0x00, 0xcd,
+            ],
+        };
+        assert_eq!(image.normalize(), Ok(Cow::Owned(expected)));
+    }
+}
@@ -1,14 +1,148 @@
-use dbus::blocking::Connection;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use dbus::arg::{PropMap, Variant};
+use dbus::blocking::stdintf::org_freedesktop_dbus::{
+    PropertiesPropertiesChanged, RequestNameReply,
+};
+use dbus::blocking::SyncConnection;
+use dbus::channel::{MatchingReceiver, Sender as _};
+use dbus::message::{MatchRule, SignalArgs};
 use gtk::glib::Sender;
 
-use crate::{ImageData, Notification, Urgency};
+use super::wire::RawNotification;
+use crate::{Notification, UrgencyEncoding};
+
+const CENTER_NAME: &str = "org.freedesktop.NotificationCenter";
+const CENTER_PATH: &str = "/org/freedesktop/NotificationCenter";
+const SERVER_NAME: &str = "org.freedesktop.Notifications";
+const SERVER_PATH: &str = "/org/freedesktop/Notifications";
+
+pub enum Event {
+    Notify(Notification),
+    Remove(u32),
+    RemoveAll,
+    DoNotDisturb(bool),
+    // whether the notification server is on the bus, sent once on start and on every change
+    Server(bool),
+    // someone else has org.freedesktop.NotificationCenter, so Notify never reaches us
+    CenterTaken,
+}
+
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct CenterState {
+    pub count: u32,
+    pub urgent_count: u32,
+    pub visible: bool,
+    pub do_not_disturb: bool,
+}
 
-pub fn run(sender: Sender<Notification>) {
-    let c = Connection::new_session().unwrap();
-    c.request_name("org.freedesktop.NotificationCenter", false, true, false)
-        .unwrap();
+// The readable side of NotificationCenter for status bars. The dashboard
+// updates it and every change goes out as PropertiesChanged.
+#[derive(Clone)]
+pub struct CenterProperties {
+    conn: Arc<SyncConnection>,
+    state: Arc<Mutex<CenterState>>,
+}
+
+impl CenterProperties {
+    pub fn update(&self, change: impl FnOnce(&mut CenterState)) {
+        let mut state = self.state.lock().unwrap();
+        let old = state.clone();
+        change(&mut state);
+        let mut changed_properties = PropMap::new();
+        if state.count != old.count {
+            changed_properties.insert("Count".into(), Variant(Box::new(state.count)));
+        }
+        if state.urgent_count != old.urgent_count {
+            changed_properties.insert("UrgentCount".into(), Variant(Box::new(state.urgent_count)));
+        }
+        if state.visible != old.visible {
+            changed_properties.insert("Visible".into(), Variant(Box::new(state.visible)));
+        }
+        if state.do_not_disturb != old.do_not_disturb {
+            changed_properties.insert(
+                "DoNotDisturb".into(),
+                Variant(Box::new(state.do_not_disturb)),
+            );
+        }
+        if changed_properties.is_empty() {
+            return;
+        }
+        let changed = PropertiesPropertiesChanged {
+            interface_name: CENTER_NAME.into(),
+            changed_properties,
+            invalidated_properties: Vec::new(),
+        };
+        let _ = self.conn.send(changed.to_emit_message(&CENTER_PATH.into()));
+    }
+}
+
+pub fn start(
+    sender: Sender<Event>,
+    encoding: UrgencyEncoding,
+) -> Result<CenterProperties, dbus::Error> {
+    let c = Arc::new(SyncConnection::new_session()?);
+    let owner_sender = Mutex::new(sender.clone());
+    c.add_match(
+        MatchRule::new_signal("org.freedesktop.DBus", "NameOwnerChanged")
+            .with_sender("org.freedesktop.DBus"),
+        move |(name, _old, new): (String, String, String), _, _| {
+            if name == SERVER_NAME {
+                let _ = owner_sender
+                    .lock()
+                    .unwrap()
+                    .send(Event::Server(!new.is_empty()));
+            }
+            true
+        },
+    )?;
+    let (running,): (bool,) = c
+        .with_proxy(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            Duration::from_millis(1000),
+        )
+        .method_call("org.freedesktop.DBus", "NameHasOwner", (SERVER_NAME,))?;
+    let _ = sender.send(Event::Server(running));
+    // closed by the app, a popup timeout or another client, the server already knows
+    let closed_sender = Mutex::new(sender.clone());
+    c.add_match(
+        MatchRule::new_signal(SERVER_NAME, "NotificationClosed"),
+        move |(id, _reason): (u32, u32), _, _| {
+            closed_sender
+                .lock()
+                .unwrap()
+                .send(Event::Remove(id))
+                .expect("Failed to send removal.");
+            true
+        },
+    )?;
+    let dnd_sender = Mutex::new(sender.clone());
+    let server = SERVER_NAME.into();
+    let path = SERVER_PATH.into();
+    c.add_match(
+        PropertiesPropertiesChanged::match_rule(Some(&server), Some(&path)).static_clone(),
+        move |changed: PropertiesPropertiesChanged, _, _| {
+            let enabled = changed
+                .changed_properties
+                .get("DoNotDisturb")
+                .and_then(|value| value.0.as_i64());
+            if let Some(enabled) = enabled {
+                dnd_sender
+                    .lock()
+                    .unwrap()
+                    .send(Event::DoNotDisturb(enabled != 0))
+                    .expect("Failed to send do not disturb state.");
+            }
+            true
+        },
+    )?;
+    let state = Arc::new(Mutex::new(CenterState::default()));
     let mut cr = dbus_crossroads::Crossroads::new();
-    let token = cr.register("org.freedesktop.NotificationCenter", |c| {
+    let token = cr.register(CENTER_NAME, |c| {
+        let notify_sender = sender.clone();
         c.method(
             "Notify",
             (
@@ -23,64 +157,62 @@ pub fn run(sender: Sender<Notification>) {
                 "image_path",
                 "progress",
                 "data",
+                "hints",
             ),
             ("reply",),
-            move |_,
-                  _,
-                  (
-                app_name,
-                replaces_id,
-                app_icon,
-                summary,
-                body,
-                actions,
-                expire_timeout,
-                urgency,
-                image_path,
-                progress,
-                raw_data,
-            ): (
-                String,
-                u32,
-                String,
-                String,
-                String,
-                Vec<String>,
-                i32,
-                i32,
-                String,
-                i32,
-                (i32, i32, i32, bool, i32, i32, Vec<u8>),
-            )| {
-                let image_data = ImageData {
-                    width: raw_data.0,
-                    height: raw_data.1,
-                    rowstride: raw_data.2,
-                    has_alpha: raw_data.3,
-                    bits_per_sample: raw_data.4,
-                    channels: raw_data.5,
-                    data: raw_data.6,
-                };
-                let notification = Notification {
-                    app_name,
-                    replaces_id,
-                    app_icon,
-                    summary,
-                    body,
-                    actions,
-                    expire_timeout,
-                    urgency: Urgency::from_i32(urgency).unwrap_or_else(|_| Urgency::Low),
-                    image_path,
-                    progress,
-                    image_data,
-                };
-                sender
-                    .send(notification)
+            move |_, _, notification: RawNotification| {
+                notify_sender
+                    .send(Event::Notify(notification.decode(encoding)))
                     .expect("Failed to send notification.");
                 Ok(("ok",))
             },
         );
+        let remove_sender = sender.clone();
+        c.method("Remove", ("id",), (), move |_, _, (id,): (u32,)| {
+            remove_sender
+                .send(Event::Remove(id))
+                .expect("Failed to send removal.");
+            Ok(())
+        });
+        let remove_all_sender = sender.clone();
+        c.method("RemoveAll", (), (), move |_, _, ()| {
+            remove_all_sender
+                .send(Event::RemoveAll)
+                .expect("Failed to send removal.");
+            Ok(())
+        });
+        c.property::<u32, _>("Count")
+            .get(|_, state: &mut Arc<Mutex<CenterState>>| Ok(state.lock().unwrap().count));
+        c.property::<u32, _>("UrgentCount")
+            .get(|_, state: &mut Arc<Mutex<CenterState>>| Ok(state.lock().unwrap().urgent_count));
+        c.property::<bool, _>("Visible")
+            .get(|_, state: &mut Arc<Mutex<CenterState>>| Ok(state.lock().unwrap().visible));
+        c.property::<bool, _>("DoNotDisturb")
+            .get(|_, state: &mut Arc<Mutex<CenterState>>| Ok(state.lock().unwrap().do_not_disturb));
+    });
+    cr.insert(CENTER_PATH, &[token], state.clone());
+    // Crossroads is not Sync, so it is locked for every incoming call
+    let cr = Mutex::new(cr);
+    c.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |msg, conn| {
+            let _ = cr.lock().unwrap().handle_message(msg, conn);
+            true
+        }),
+    );
+    // the properties still work for whoever asks this connection directly
+    match c.request_name(CENTER_NAME, false, true, true)? {
+        RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => {}
+        _ => {
+            let _ = sender.send(Event::CenterTaken);
+        }
+    }
+    let conn = c.clone();
+    thread::spawn(move || loop {
+        if let Err(error) = conn.process(Duration::from_millis(1000)) {
+            eprintln!("Lost the session bus: {}", error);
+            break;
+        }
     });
-    cr.insert("/org/freedesktop/NotificationCenter", &[token], ());
-    cr.serve(&c).unwrap();
+    Ok(CenterProperties { conn: c, state })
 }
@@ -0,0 +1,488 @@
+// Parses the body markup subset of the Desktop Notifications spec:
+// <b>, <i>, <u>, <a href="...">, <img src="..." alt="..."> and <br>.
+// Everything else is escaped, so the result can be fed to Label::set_markup.
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Link {
+    pub href: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BodyImage {
+    pub src: String,
+    pub alt: String,
+}
+
+impl BodyImage {
+    pub fn path(&self) -> Option<&str> {
+        if let Some(path) = self.src.strip_prefix("file://") {
+            return Some(path);
+        }
+        if self.src.starts_with('/') {
+            return Some(&self.src);
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Markup {
+    pub markup: String,
+    pub text: String,
+    pub links: Vec<Link>,
+    pub images: Vec<BodyImage>,
+}
+
+impl Markup {
+    pub fn image_path(&self) -> Option<&str> {
+        self.images.iter().find_map(|image| image.path())
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Tag {
+    Bold,
+    Italic,
+    Underline,
+    Link,
+}
+
+impl Tag {
+    fn from_name(name: &str) -> Option<Tag> {
+        match name {
+            "b" => Some(Tag::Bold),
+            "i" => Some(Tag::Italic),
+            "u" => Some(Tag::Underline),
+            "a" => Some(Tag::Link),
+            _ => None,
+        }
+    }
+    fn close(&self) -> &'static str {
+        match self {
+            Tag::Bold => "</b>",
+            Tag::Italic => "</i>",
+            Tag::Underline => "</u>",
+            Tag::Link => "</a>",
+        }
+    }
+}
+
+struct RawTag {
+    name: String,
+    closing: bool,
+    attributes: Vec<(String, String)>,
+}
+
+impl RawTag {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub fn parse(input: &str) -> Markup {
+    let mut ret = Markup::default();
+    let mut stack: Vec<Tag> = Vec::new();
+    let mut link: Option<Link> = None;
+    let mut rest = input;
+    while let Some(char) = rest.chars().next() {
+        match char {
+            '<' => {
+                let tag = rest.find('>').and_then(|end| {
+                    parse_tag(&rest[1..end]).map(|tag| (tag, end))
+                });
+                let Some((tag, end)) = tag else {
+                    push_text(&mut ret, &mut link, '<');
+                    rest = &rest[1..];
+                    continue;
+                };
+                let raw = &rest[..=end];
+                rest = &rest[end + 1..];
+                if tag.name == "br" {
+                    push_text(&mut ret, &mut link, '\n');
+                } else if tag.name == "img" {
+                    let image = BodyImage {
+                        src: decode_entities(tag.attribute("src").unwrap_or_default()),
+                        alt: decode_entities(tag.attribute("alt").unwrap_or_default()),
+                    };
+                    for char in image.alt.chars() {
+                        push_text(&mut ret, &mut link, char);
+                    }
+                    ret.images.push(image);
+                } else if let Some(kind) = Tag::from_name(&tag.name) {
+                    if tag.closing {
+                        close_tag(&mut ret, &mut stack, &mut link, kind);
+                    } else {
+                        open_tag(&mut ret, &mut stack, &mut link, kind, &tag);
+                    }
+                } else {
+                    for char in raw.chars() {
+                        push_text(&mut ret, &mut link, char);
+                    }
+                }
+            }
+            '&' => {
+                let (decoded, len) = decode_entity(rest).unwrap_or(('&', 1));
+                push_text(&mut ret, &mut link, decoded);
+                rest = &rest[len..];
+            }
+            _ => {
+                push_text(&mut ret, &mut link, char);
+                rest = &rest[char.len_utf8()..];
+            }
+        }
+    }
+    while let Some(tag) = stack.pop() {
+        if tag == Tag::Link {
+            ret.links.extend(link.take());
+        }
+        ret.markup.push_str(tag.close());
+    }
+    ret
+}
+
+fn open_tag(
+    ret: &mut Markup,
+    stack: &mut Vec<Tag>,
+    link: &mut Option<Link>,
+    kind: Tag,
+    tag: &RawTag,
+) {
+    if kind == Tag::Link {
+        if link.is_some() {
+            return;
+        }
+        let href = decode_entities(tag.attribute("href").unwrap_or_default());
+        ret.markup.push_str("<a href=\"");
+        ret.markup.push_str(&escape(&href));
+        ret.markup.push_str("\">");
+        *link = Some(Link {
+            href,
+            text: String::new(),
+        });
+    } else {
+        ret.markup.push_str(match kind {
+            Tag::Bold => "<b>",
+            Tag::Italic => "<i>",
+            _ => "<u>",
+        });
+    }
+    stack.push(kind);
+}
+
+fn close_tag(ret: &mut Markup, stack: &mut Vec<Tag>, link: &mut Option<Link>, kind: Tag) {
+    if !stack.contains(&kind) {
+        return;
+    }
+    // pango rejects overlapping tags, so close everything opened after this one
+    while let Some(tag) = stack.pop() {
+        if tag == Tag::Link {
+            ret.links.extend(link.take());
+        }
+        ret.markup.push_str(tag.close());
+        if tag == kind {
+            break;
+        }
+    }
+}
+
+fn push_text(ret: &mut Markup, link: &mut Option<Link>, char: char) {
+    match char {
+        '&' => ret.markup.push_str("&amp;"),
+        '<' => ret.markup.push_str("&lt;"),
+        '>' => ret.markup.push_str("&gt;"),
+        '"' => ret.markup.push_str("&quot;"),
+        '\'' => ret.markup.push_str("&apos;"),
+        _ => ret.markup.push(char),
+    }
+    ret.text.push(char);
+    if let Some(link) = link {
+        link.text.push(char);
+    }
+}
+
+fn parse_tag(content: &str) -> Option<RawTag> {
+    let content = content.trim_end();
+    let (closing, content) = match content.strip_prefix('/') {
+        Some(content) => (true, content),
+        None => (false, content),
+    };
+    let content = content.strip_suffix('/').unwrap_or(content);
+    if !content.starts_with(|char: char| char.is_ascii_alphabetic()) {
+        return None;
+    }
+    let name_end = content
+        .find(|char: char| !char.is_ascii_alphanumeric())
+        .unwrap_or(content.len());
+    if !content[name_end..].starts_with(|char: char| char.is_whitespace())
+        && name_end != content.len()
+    {
+        return None;
+    }
+    let name = content[..name_end].to_ascii_lowercase();
+    let mut attributes = Vec::new();
+    let mut rest = content[name_end..].trim_start();
+    while !rest.is_empty() {
+        let key_end = rest
+            .find(|char: char| char == '=' || char.is_whitespace())
+            .unwrap_or(rest.len());
+        let key = rest[..key_end].to_ascii_lowercase();
+        rest = rest[key_end..].trim_start();
+        let mut value = "";
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let end = after[1..].find(quote)? + 1;
+                    value = &after[1..end];
+                    rest = &after[end + 1..];
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    value = &after[..end];
+                    rest = &after[end..];
+                }
+            }
+        }
+        if !key.is_empty() {
+            attributes.push((key, value.to_string()));
+        }
+        rest = rest.trim_start();
+    }
+    Some(RawTag {
+        name,
+        closing,
+        attributes,
+    })
+}
+
+fn decode_entity(input: &str) -> Option<(char, usize)> {
+    let end = input.find(';')?;
+    if end > 10 {
+        return None;
+    }
+    let entity = &input[1..end];
+    let decoded = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        _ => {
+            let number = entity.strip_prefix('#')?;
+            let value = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(value).filter(|&char| is_xml_char(char))?
+        }
+    };
+    Some((decoded, end + 1))
+}
+
+// pango takes XML, a NUL would also cut the label short
+fn is_xml_char(char: char) -> bool {
+    matches!(char, '\t' | '\n' | '\r' | ' '..='\u{d7ff}' | '\u{e000}'..='\u{fffd}' | '\u{10000}'..)
+}
+
+fn decode_entities(input: &str) -> String {
+    let mut ret = String::new();
+    let mut rest = input;
+    while let Some(char) = rest.chars().next() {
+        if char == '&' {
+            let (decoded, len) = decode_entity(rest).unwrap_or(('&', 1));
+            ret.push(decoded);
+            rest = &rest[len..];
+        } else {
+            ret.push(char);
+            rest = &rest[char.len_utf8()..];
+        }
+    }
+    ret
+}
+
+fn escape(input: &str) -> String {
+    let mut ret = String::new();
+    for char in input.chars() {
+        match char {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&apos;"),
+            _ => ret.push(char),
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(href: &str, text: &str) -> Link {
+        Link {
+            href: href.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn escapes_unknown_tags_and_stray_characters() {
+        let cases = [
+            (
+                "<span>hi</span>",
+                "&lt;span&gt;hi&lt;/span&gt;",
+                "<span>hi</span>",
+            ),
+            ("a < b & c", "a &lt; b &amp; c", "a < b & c"),
+            ("1<2", "1&lt;2", "1<2"),
+            ("<", "&lt;", "<"),
+            ("&", "&amp;", "&"),
+            ("&unknown;", "&amp;unknown;", "&unknown;"),
+            (
+                "it's \"quoted\"",
+                "it&apos;s &quot;quoted&quot;",
+                "it's \"quoted\"",
+            ),
+            ("<b>bold</b>", "<b>bold</b>", "bold"),
+            ("<B>bold</B>", "<b>bold</b>", "bold"),
+        ];
+        for (input, markup, text) in cases {
+            let parsed = parse(input);
+            assert_eq!(parsed.markup, markup, "{}", input);
+            assert_eq!(parsed.text, text, "{}", input);
+        }
+    }
+
+    #[test]
+    fn balances_tags() {
+        let cases = [
+            ("<b><i>x</b>y</i>", "<b><i>x</i></b>y"),
+            ("<b>x<i>y", "<b>x<i>y</i></b>"),
+            ("x</b>", "x"),
+            ("<u>a</u></u>", "<u>a</u>"),
+        ];
+        for (input, markup) in cases {
+            assert_eq!(parse(input).markup, markup, "{}", input);
+        }
+    }
+
+    #[test]
+    fn collects_links() {
+        let parsed = parse(
+            "<a href=\"https://a.example/?x=1&amp;y=2\">first</a> and <a href='b'>second</a>",
+        );
+        assert_eq!(
+            parsed.markup,
+            "<a href=\"https://a.example/?x=1&amp;y=2\">first</a> and <a href=\"b\">second</a>"
+        );
+        assert_eq!(
+            parsed.links,
+            [
+                link("https://a.example/?x=1&y=2", "first"),
+                link("b", "second")
+            ]
+        );
+        assert_eq!(parsed.text, "first and second");
+    }
+
+    #[test]
+    fn ignores_nested_links() {
+        let parsed = parse("<a href=outer>a <a href=inner>b</a> c</a>");
+        assert_eq!(parsed.markup, "<a href=\"outer\">a b</a> c");
+        assert_eq!(parsed.links, [link("outer", "a b")]);
+    }
+
+    #[test]
+    fn closes_unclosed_links() {
+        let parsed = parse("<a href=x><b>open");
+        assert_eq!(parsed.markup, "<a href=\"x\"><b>open</b></a>");
+        assert_eq!(parsed.links, [link("x", "open")]);
+    }
+
+    #[test]
+    fn reads_attributes() {
+        let cases = [
+            ("<a href=\"x y\">", "x y"),
+            ("<a href='x\"y'>", "x\"y"),
+            ("<a href=plain>", "plain"),
+            ("<a  HREF = spaced >", "spaced"),
+            ("<a title=t href=second>", "second"),
+            ("<a>", ""),
+        ];
+        for (input, href) in cases {
+            assert_eq!(parse(input).links, [link(href, "")], "{}", input);
+        }
+        // an unterminated quote is not a tag
+        assert_eq!(parse("<a href=\"x>y").markup, "&lt;a href=&quot;x&gt;y");
+    }
+
+    #[test]
+    fn decodes_entities() {
+        let cases = [
+            ("&amp;&lt;&gt;&quot;&apos;", "&<>\"'"),
+            ("&nbsp;", "\u{a0}"),
+            ("&#65;&#x42;&#X43;", "ABC"),
+            ("&#x1F600;", "\u{1f600}"),
+            ("&#xD800;", "&#xD800;"),
+            ("&#;", "&#;"),
+            ("&#0;", "&#0;"),
+            ("&#x0;", "&#x0;"),
+            ("&#1;", "&#1;"),
+            ("&#x1F;", "&#x1F;"),
+            ("&#xFFFE;", "&#xFFFE;"),
+            ("&#xFFFF;", "&#xFFFF;"),
+            ("&#9;&#10;&#13;", "\t\n\r"),
+            ("& amp;", "& amp;"),
+        ];
+        for (input, text) in cases {
+            assert_eq!(parse(input).text, text, "{}", input);
+        }
+        assert_eq!(parse("&#60;b&#62;").markup, "&lt;b&gt;");
+        assert_eq!(parse("a&#0;b").markup, "a&amp;#0;b");
+    }
+
+    #[test]
+    fn replaces_images_with_their_alt_text() {
+        let parsed = parse("see <img src=\"file:///tmp/a b.png\" alt=\"a &amp; b\"/> here");
+        assert_eq!(parsed.markup, "see a &amp; b here");
+        assert_eq!(parsed.text, "see a & b here");
+        assert_eq!(
+            parsed.images,
+            [BodyImage {
+                src: "file:///tmp/a b.png".to_string(),
+                alt: "a & b".to_string(),
+            }]
+        );
+        assert_eq!(parsed.image_path(), Some("/tmp/a b.png"));
+    }
+
+    #[test]
+    fn finds_local_image_paths() {
+        let cases = [
+            ("<img src=file:///a.png>", Some("/a.png")),
+            ("<img src=/b.png>", Some("/b.png")),
+            ("<img src=https://c.example/c.png>", None),
+            ("<img alt=nothing>", None),
+            (
+                "<img src=https://d.example/d.png><img src=/e.png>",
+                Some("/e.png"),
+            ),
+        ];
+        for (input, path) in cases {
+            assert_eq!(parse(input).image_path(), path, "{}", input);
+        }
+    }
+
+    #[test]
+    fn turns_br_into_newlines() {
+        let parsed = parse("a<br>b<br/>c<BR />d");
+        assert_eq!(parsed.markup, "a\nb\nc\nd");
+        assert_eq!(parsed.text, "a\nb\nc\nd");
+    }
+}
@@ -1,5 +1,20 @@
+pub mod cli;
+pub mod client;
+pub mod config;
+pub mod daemon;
+pub mod dnd;
+pub mod export;
+pub mod image;
 pub mod listener;
+pub mod markup;
 mod notificationbutton;
+pub mod position;
+pub mod sort;
+pub mod time;
+mod wire;
+
+#[cfg(test)]
+mod tests;
 
 use glib::Object;
 use gtk::glib;
@@ -0,0 +1,47 @@
+use std::str::FromStr;
+
+use gtk4_layer_shell::Edge;
+
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub enum Position {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    // full height sidebars
+    Left,
+    Right,
+}
+
+impl Position {
+    pub fn anchors(&self) -> &'static [Edge] {
+        match self {
+            Position::TopLeft => &[Edge::Top, Edge::Left],
+            Position::TopRight => &[Edge::Top, Edge::Right],
+            Position::BottomLeft => &[Edge::Bottom, Edge::Left],
+            Position::BottomRight => &[Edge::Bottom, Edge::Right],
+            Position::Left => &[Edge::Top, Edge::Bottom, Edge::Left],
+            Position::Right => &[Edge::Top, Edge::Bottom, Edge::Right],
+        }
+    }
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top-left" => Ok(Position::TopLeft),
+            "top-right" => Ok(Position::TopRight),
+            "bottom-left" => Ok(Position::BottomLeft),
+            "bottom-right" => Ok(Position::BottomRight),
+            "left" => Ok(Position::Left),
+            "right" => Ok(Position::Right),
+            _ => Err(format!(
+                "invalid position {}, only top-left, top-right, bottom-left, bottom-right, left or right allowed",
+                s
+            )),
+        }
+    }
+}
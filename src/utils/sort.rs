@@ -0,0 +1,68 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use gtk::subclass::prelude::ObjectSubclassIsExt;
+
+use crate::notibox::NotiBox;
+
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub enum SortMode {
+    #[default]
+    NewestFirst,
+    OldestFirst,
+    Urgency,
+    App,
+}
+
+impl SortMode {
+    // same order as the entries of the sort dropdown in window.ui
+    const ALL: [SortMode; 4] = [
+        SortMode::NewestFirst,
+        SortMode::OldestFirst,
+        SortMode::Urgency,
+        SortMode::App,
+    ];
+
+    pub fn from_index(index: u32) -> Option<SortMode> {
+        Self::ALL.get(index as usize).copied()
+    }
+    pub fn index(&self) -> u32 {
+        Self::ALL.iter().position(|mode| mode == self).unwrap() as u32
+    }
+    pub fn compare(&self, a: &NotiBox, b: &NotiBox) -> Ordering {
+        let (a, b) = (a.imp(), b.imp());
+        let newest = b
+            .received
+            .get()
+            .cmp(&a.received.get())
+            .then(b.id.get().cmp(&a.id.get()));
+        match self {
+            SortMode::NewestFirst => newest,
+            SortMode::OldestFirst => newest.reverse(),
+            SortMode::Urgency => b.urgency.borrow().cmp(&a.urgency.borrow()).then(newest),
+            SortMode::App => a
+                .app_name
+                .borrow()
+                .to_lowercase()
+                .cmp(&b.app_name.borrow().to_lowercase())
+                .then(newest),
+        }
+    }
+}
+
+impl FromStr for SortMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "newest" => Ok(SortMode::NewestFirst),
+            "oldest" => Ok(SortMode::OldestFirst),
+            "urgency" => Ok(SortMode::Urgency),
+            "app" => Ok(SortMode::App),
+            _ => Err(format!(
+                "invalid sort mode {}, only newest, oldest, urgency or app allowed",
+                s
+            )),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use std::env;
+use std::process::Command;
+use std::thread;
+
+const INSIDE: &str = "OXIDASH_PRIVATE_BUS";
+
+// libdbus reads DBUS_SESSION_BUS_ADDRESS only once per process and the well
+// known names are global, so every test runs again in its own process under
+// dbus-run-session, which also takes the daemon down afterwards.
+pub fn on_private_bus(test: impl FnOnce()) {
+    if env::var_os(INSIDE).is_some() {
+        test();
+        return;
+    }
+    let name = thread::current()
+        .name()
+        .expect("tests run on threads named after them")
+        .to_string();
+    let status = Command::new("dbus-run-session")
+        .arg("--")
+        .arg(env::current_exe().unwrap())
+        .args(["--exact", &name, "--nocapture"])
+        .env(INSIDE, "1")
+        .status()
+        .unwrap_or_else(|error| panic!("{} needs dbus-run-session: {}", name, error));
+    assert!(status.success(), "{} failed on the private bus", name);
+}
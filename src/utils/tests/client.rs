@@ -0,0 +1,165 @@
+use super::bus::on_private_bus;
+use super::mock::{Call, MockServer};
+use super::{channel, notification};
+use crate::utils::client::{Client, Reply, Server};
+use crate::{Notification, Urgency, UrgencyEncoding};
+
+#[test]
+fn get_all_decodes_hinted_notifications() {
+    on_private_bus(|| {
+        let mock = MockServer::start();
+        mock.script(|script| {
+            script.notifications = vec![notification(1, "first"), notification(2, "second")]
+        });
+        let server = Server::connect().unwrap();
+        let notifications = server.get_all(UrgencyEncoding::Spec).unwrap();
+        assert_eq!(notifications.len(), 2);
+        let first = &notifications[0];
+        assert_eq!(first.replaces_id, 1);
+        assert_eq!(first.summary, "first");
+        assert_eq!(first.app_name, "mail");
+        assert_eq!(first.actions, vec!["default", "Open"]);
+        assert_eq!(first.urgency, Urgency::Urgent);
+        assert_eq!(first.received, 1_700_000_000);
+        assert_eq!(first.image_data, notification(1, "first").image_data);
+        assert_eq!(mock.calls(), vec![Call::GetAll]);
+    });
+}
+
+#[test]
+fn get_all_decodes_what_oxinoti_sends() {
+    on_private_bus(|| {
+        let mock = MockServer::start_oxinoti();
+        let urgencies = [Urgency::Low, Urgency::Normal, Urgency::Urgent];
+        mock.script(|script| {
+            script.notifications = urgencies
+                .iter()
+                .enumerate()
+                .map(|(id, urgency)| Notification {
+                    urgency: urgency.clone(),
+                    ..notification(id as u32 + 1, "old")
+                })
+                .collect()
+        });
+        let server = Server::connect().unwrap();
+        let notifications = server.get_all(UrgencyEncoding::Oxinoti).unwrap();
+        assert_eq!(notifications.len(), 3);
+        for (notification, urgency) in notifications.iter().zip(urgencies) {
+            assert_eq!(notification.urgency, urgency);
+            assert_eq!(notification.summary, "old");
+            assert!(notification.hints.is_empty());
+        }
+        assert_eq!(
+            notifications[0].image_data,
+            notification(1, "old").image_data
+        );
+    });
+}
+
+#[test]
+fn server_calls_reach_the_server() {
+    on_private_bus(|| {
+        let mock = MockServer::start();
+        let server = Server::connect().unwrap();
+        server.dismiss(3).unwrap();
+        server.inline_reply(4, "on my way").unwrap();
+        server.invoke_action(5, "default").unwrap();
+        server.clear_all().unwrap();
+        assert_eq!(
+            mock.calls(),
+            vec![
+                Call::Remove(3),
+                Call::InlineReply(4, "on my way".into()),
+                Call::InvokeAction(5, "default".into()),
+                Call::ClearAll,
+            ]
+        );
+    });
+}
+
+#[test]
+fn dismiss_falls_back_to_close_notification() {
+    on_private_bus(|| {
+        let mock = MockServer::start();
+        mock.script(|script| script.no_remove_notification = true);
+        Server::connect().unwrap().dismiss(3).unwrap();
+        assert_eq!(mock.calls(), vec![Call::Close(3)]);
+    });
+}
+
+#[test]
+fn toggle_notification_center_returns_the_new_state() {
+    on_private_bus(|| {
+        let mock = MockServer::start();
+        let server = Server::connect().unwrap();
+        assert!(server.toggle_notification_center().unwrap());
+        assert!(!server.toggle_notification_center().unwrap());
+        mock.script(|script| script.center_open = false);
+        assert!(server.toggle_notification_center().unwrap());
+    });
+}
+
+#[test]
+fn set_dnd_only_toggles_when_needed() {
+    on_private_bus(|| {
+        let mock = MockServer::start();
+        let server = Server::connect().unwrap();
+        server.set_dnd(true).unwrap();
+        server.set_dnd(true).unwrap();
+        assert!(server.do_not_disturb().unwrap());
+        assert_eq!(mock.calls(), vec![Call::ToggleDnd]);
+    });
+}
+
+#[test]
+fn client_replies_with_the_dnd_state() {
+    on_private_bus(|| {
+        let mock = MockServer::start();
+        mock.script(|script| script.do_not_disturb = true);
+        let (sender, replies) = channel();
+        let client = Client::new(sender, UrgencyEncoding::Spec);
+        client.toggle_dnd();
+        assert!(matches!(replies.next(), Reply::DoNotDisturb(Some(false))));
+    });
+}
+
+#[test]
+fn set_dnd_needs_the_property() {
+    on_private_bus(|| {
+        let mock = MockServer::start();
+        mock.script(|script| script.no_dnd_property = true);
+        let server = Server::connect().unwrap();
+        let error = server.set_dnd(true).unwrap_err();
+        assert_eq!(
+            error.name(),
+            Some("org.freedesktop.DBus.Error.NotSupported")
+        );
+        server.toggle_dnd().unwrap();
+        assert_eq!(mock.calls(), vec![Call::ToggleDnd]);
+    });
+}
+
+#[test]
+fn client_replies_without_a_dnd_state() {
+    on_private_bus(|| {
+        let mock = MockServer::start();
+        mock.script(|script| script.no_dnd_property = true);
+        let (sender, replies) = channel();
+        let client = Client::new(sender, UrgencyEncoding::Spec);
+        client.get_dnd();
+        assert!(matches!(replies.next(), Reply::DoNotDisturb(None)));
+    });
+}
+
+#[test]
+fn client_reports_a_missing_server() {
+    on_private_bus(|| {
+        let (sender, replies) = channel();
+        let client = Client::new(sender, UrgencyEncoding::Spec);
+        client.dismiss(1);
+        match replies.next() {
+            Reply::Failed(error) => assert_eq!(error.what, "dismiss notification"),
+            _ => panic!("expected a failure"),
+        }
+    });
+}
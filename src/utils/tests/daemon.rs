@@ -0,0 +1,95 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dbus::arg::{PropMap, Variant};
+use dbus::blocking::Connection;
+use dbus::message::MatchRule;
+
+use super::bus::on_private_bus;
+use super::channel;
+use crate::utils::client::Server;
+use crate::utils::daemon;
+use crate::utils::listener::Event;
+use crate::UrgencyEncoding;
+
+const SERVER_NAME: &str = "org.freedesktop.Notifications";
+const SERVER_PATH: &str = "/org/freedesktop/Notifications";
+const TIMEOUT: Duration = Duration::from_millis(1000);
+
+fn notify(c: &Connection, summary: &str, hints: PropMap) -> u32 {
+    let proxy = c.with_proxy(SERVER_NAME, SERVER_PATH, TIMEOUT);
+    let (id,): (u32,) = proxy
+        .method_call(
+            SERVER_NAME,
+            "Notify",
+            (
+                "app",
+                0u32,
+                "",
+                summary,
+                "",
+                Vec::<String>::new(),
+                hints,
+                -1,
+            ),
+        )
+        .unwrap();
+    id
+}
+
+#[test]
+fn transient_notifications_are_not_kept() {
+    on_private_bus(|| {
+        let (sender, events) = channel();
+        let connection = daemon::claim().unwrap();
+        thread::spawn(move || daemon::serve(connection, sender, UrgencyEncoding::Spec));
+        let c = Connection::new_session().unwrap();
+        notify(&c, "kept", PropMap::new());
+        let mut hints = PropMap::new();
+        hints.insert("transient".into(), Variant(Box::new(true)));
+        notify(&c, "gone", hints);
+        // both still pop up
+        assert!(matches!(events.next(), Event::Notify(_)));
+        assert!(matches!(events.next(), Event::Notify(_)));
+        let kept = Server::connect()
+            .unwrap()
+            .get_all(UrgencyEncoding::Spec)
+            .unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].summary, "kept");
+    });
+}
+
+#[test]
+fn dismissing_and_closing_report_their_reasons() {
+    on_private_bus(|| {
+        let (sender, _events) = channel();
+        let connection = daemon::claim().unwrap();
+        thread::spawn(move || daemon::serve(connection, sender, UrgencyEncoding::Spec));
+        let c = Connection::new_session().unwrap();
+        let closed = Arc::new(Mutex::new(Vec::new()));
+        let received = closed.clone();
+        c.add_match(
+            MatchRule::new_signal(SERVER_NAME, "NotificationClosed"),
+            move |(id, reason): (u32, u32), _, _| {
+                received.lock().unwrap().push((id, reason));
+                true
+            },
+        )
+        .unwrap();
+        let dismissed = notify(&c, "dismissed", PropMap::new());
+        let retracted = notify(&c, "retracted", PropMap::new());
+        Server::connect().unwrap().dismiss(dismissed).unwrap();
+        // what an app calls for its own notification
+        c.with_proxy(SERVER_NAME, SERVER_PATH, TIMEOUT)
+            .method_call::<(), _, _, _>(SERVER_NAME, "CloseNotification", (retracted,))
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while closed.lock().unwrap().len() < 2 {
+            assert!(Instant::now() < deadline, "nothing was closed in time");
+            c.process(Duration::from_millis(10)).unwrap();
+        }
+        assert_eq!(*closed.lock().unwrap(), [(dismissed, 2), (retracted, 3)]);
+    });
+}
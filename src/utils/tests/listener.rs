@@ -0,0 +1,201 @@
+use std::time::Duration;
+
+use dbus::arg::PropMap;
+use dbus::blocking::Connection;
+
+use super::bus::on_private_bus;
+use super::mock::MockServer;
+use super::{channel, notification};
+use crate::utils::client::Server;
+use crate::utils::listener::{self, Event};
+use crate::{Urgency, UrgencyEncoding};
+
+const CENTER_NAME: &str = "org.freedesktop.NotificationCenter";
+const CENTER_PATH: &str = "/org/freedesktop/NotificationCenter";
+
+#[test]
+fn notify_is_decoded() {
+    on_private_bus(|| {
+        let _mock = MockServer::start();
+        let (sender, events) = channel();
+        let _properties = listener::start(sender, UrgencyEncoding::Spec).unwrap();
+        assert!(matches!(events.next(), Event::Server(true)));
+
+        let mut hints = PropMap::new();
+        hints.insert("urgency".into(), dbus::arg::Variant(Box::new(0u8)));
+        let c = Connection::new_session().unwrap();
+        let proxy = c.with_proxy(CENTER_NAME, CENTER_PATH, Duration::from_millis(1000));
+        let image = notification(9, "").image_data;
+        let (reply,): (String,) = proxy
+            .method_call(
+                CENTER_NAME,
+                "Notify",
+                (
+                    "chat",
+                    9u32,
+                    "chat-icon",
+                    "hello",
+                    "there",
+                    vec!["inline-reply", "Reply"],
+                    5000,
+                    2,
+                    "/tmp/image.png",
+                    40,
+                    (
+                        image.width,
+                        image.height,
+                        image.rowstride,
+                        image.has_alpha,
+                        image.bits_per_sample,
+                        image.channels,
+                        image.data.clone(),
+                    ),
+                    hints,
+                ),
+            )
+            .unwrap();
+        assert_eq!(reply, "ok");
+        let Event::Notify(notification) = events.next() else {
+            panic!("expected a notification");
+        };
+        assert_eq!(notification.app_name, "chat");
+        assert_eq!(notification.replaces_id, 9);
+        assert_eq!(notification.summary, "hello");
+        assert_eq!(notification.body, "there");
+        assert_eq!(notification.actions, vec!["inline-reply", "Reply"]);
+        assert_eq!(notification.expire_timeout, 5000);
+        assert_eq!(notification.image_path, "/tmp/image.png");
+        assert_eq!(notification.progress, 40);
+        assert_eq!(notification.image_data, image);
+        // the hint wins over the urgency argument
+        assert_eq!(notification.urgency, Urgency::Low);
+    });
+}
+
+#[test]
+fn notify_without_hints_is_decoded() {
+    on_private_bus(|| {
+        let _mock = MockServer::start();
+        let (sender, events) = channel();
+        let _properties = listener::start(sender, UrgencyEncoding::Oxinoti).unwrap();
+        assert!(matches!(events.next(), Event::Server(true)));
+
+        // the eleven fields oxinoti sends, without the hints dictionary
+        let c = Connection::new_session().unwrap();
+        let proxy = c.with_proxy(CENTER_NAME, CENTER_PATH, Duration::from_millis(1000));
+        let image = notification(3, "").image_data;
+        let (reply,): (String,) = proxy
+            .method_call(
+                CENTER_NAME,
+                "Notify",
+                (
+                    "mail",
+                    3u32,
+                    "mail-unread",
+                    "old",
+                    "format",
+                    Vec::<String>::new(),
+                    -1,
+                    3,
+                    "",
+                    -1,
+                    (
+                        image.width,
+                        image.height,
+                        image.rowstride,
+                        image.has_alpha,
+                        image.bits_per_sample,
+                        image.channels,
+                        image.data.clone(),
+                    ),
+                ),
+            )
+            .unwrap();
+        assert_eq!(reply, "ok");
+        let Event::Notify(notification) = events.next() else {
+            panic!("expected a notification");
+        };
+        assert_eq!(notification.replaces_id, 3);
+        assert_eq!(notification.summary, "old");
+        assert_eq!(notification.image_data, image);
+        assert!(notification.hints.is_empty());
+        // oxinoti's 3 is critical
+        assert_eq!(notification.urgency, Urgency::Urgent);
+    });
+}
+
+#[test]
+fn notify_with_something_else_than_hints_is_rejected() {
+    on_private_bus(|| {
+        let _mock = MockServer::start();
+        let (sender, events) = channel();
+        let _properties = listener::start(sender, UrgencyEncoding::Oxinoti).unwrap();
+        assert!(matches!(events.next(), Event::Server(true)));
+
+        let c = Connection::new_session().unwrap();
+        let proxy = c.with_proxy(CENTER_NAME, CENTER_PATH, Duration::from_millis(1000));
+        let image = notification(3, "").image_data;
+        let result: Result<(String,), _> = proxy.method_call(
+            CENTER_NAME,
+            "Notify",
+            (
+                "mail",
+                3u32,
+                "mail-unread",
+                "broken",
+                "hints",
+                Vec::<String>::new(),
+                -1,
+                2,
+                "",
+                -1,
+                (
+                    image.width,
+                    image.height,
+                    image.rowstride,
+                    image.has_alpha,
+                    image.bits_per_sample,
+                    image.channels,
+                    image.data,
+                ),
+                "not a dictionary",
+            ),
+        );
+        assert!(result.is_err());
+    });
+}
+
+#[test]
+fn closed_notifications_are_removed() {
+    on_private_bus(|| {
+        let _mock = MockServer::start();
+        let (sender, events) = channel();
+        let _properties = listener::start(sender, UrgencyEncoding::Spec).unwrap();
+        assert!(matches!(events.next(), Event::Server(true)));
+        Server::connect().unwrap().dismiss(4).unwrap();
+        assert!(matches!(events.next(), Event::Remove(4)));
+    });
+}
+
+#[test]
+fn server_coming_and_going_is_reported() {
+    on_private_bus(|| {
+        let (sender, events) = channel();
+        let _properties = listener::start(sender, UrgencyEncoding::Spec).unwrap();
+        assert!(matches!(events.next(), Event::Server(false)));
+        let _mock = MockServer::start();
+        assert!(matches!(events.next(), Event::Server(true)));
+    });
+}
+
+#[test]
+fn a_taken_center_name_is_reported() {
+    on_private_bus(|| {
+        let other = Connection::new_session().unwrap();
+        other.request_name(CENTER_NAME, false, false, true).unwrap();
+        let (sender, events) = channel();
+        let _properties = listener::start(sender, UrgencyEncoding::Spec).unwrap();
+        assert!(matches!(events.next(), Event::Server(false)));
+        assert!(matches!(events.next(), Event::CenterTaken));
+    });
+}
@@ -0,0 +1,263 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use dbus::arg::{Append, Arg, PropMap, Variant};
+use dbus::blocking::Connection;
+use dbus::MethodErr;
+use dbus_crossroads::Crossroads;
+
+use crate::{ImageData, Notification, UrgencyEncoding};
+
+const SERVER_NAME: &str = "org.freedesktop.Notifications";
+const SERVER_PATH: &str = "/org/freedesktop/Notifications";
+
+type ImageFields = (i32, i32, i32, bool, i32, i32, Vec<u8>);
+
+// a notification the way oxinoti puts it into GetAllNotifications today,
+// without hints and with urgencies counted from 1
+type OxinotiFields = (
+    String,
+    u32,
+    String,
+    String,
+    String,
+    Vec<String>,
+    i32,
+    i32,
+    String,
+    i32,
+    ImageFields,
+);
+
+// the same with spec urgencies and the hints dictionary appended
+type HintedFields = (
+    String,
+    u32,
+    String,
+    String,
+    String,
+    Vec<String>,
+    i32,
+    i32,
+    String,
+    i32,
+    ImageFields,
+    PropMap,
+);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Call {
+    Close(u32),
+    Remove(u32),
+    ClearAll,
+    ToggleDnd,
+    InlineReply(u32, String),
+    InvokeAction(u32, String),
+    GetAll,
+    ToggleNotificationCenter,
+}
+
+// What the mock answers with, tests change it through MockServer::script.
+#[derive(Default)]
+pub struct Script {
+    pub notifications: Vec<Notification>,
+    pub do_not_disturb: bool,
+    // like servers that can only toggle it
+    pub no_dnd_property: bool,
+    // like servers that only know CloseNotification
+    pub no_remove_notification: bool,
+    pub center_open: bool,
+}
+
+#[derive(Default)]
+struct State {
+    script: Script,
+    calls: Vec<Call>,
+}
+
+// Stands in for oxinoti on the private bus and records every call it gets.
+pub struct MockServer {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockServer {
+    pub fn start() -> MockServer {
+        MockServer::serve(hinted_fields)
+    }
+    pub fn start_oxinoti() -> MockServer {
+        MockServer::serve(oxinoti_fields)
+    }
+    fn serve<F>(fields: fn(&Notification) -> F) -> MockServer
+    where
+        F: Arg + Append + 'static,
+    {
+        let c = Connection::new_session().expect("mock could not connect");
+        c.request_name(SERVER_NAME, false, true, false)
+            .expect("mock could not claim the server name");
+        let state = Arc::new(Mutex::new(State::default()));
+        let mut cr = Crossroads::new();
+        let token = cr.register(SERVER_NAME, |b| {
+            b.signal::<(u32, u32), _>("NotificationClosed", ("id", "reason"));
+            b.method(
+                "CloseNotification",
+                ("id",),
+                (),
+                |ctx, state: &mut Arc<Mutex<State>>, (id,): (u32,)| {
+                    state.lock().unwrap().calls.push(Call::Close(id));
+                    ctx.push_msg(ctx.make_signal("NotificationClosed", (id, 3u32)));
+                    Ok(())
+                },
+            );
+            b.method(
+                "RemoveNotification",
+                ("id",),
+                (),
+                |ctx, state: &mut Arc<Mutex<State>>, (id,): (u32,)| {
+                    let mut state = state.lock().unwrap();
+                    if state.script.no_remove_notification {
+                        return Err(MethodErr::no_method("RemoveNotification"));
+                    }
+                    state.calls.push(Call::Remove(id));
+                    ctx.push_msg(ctx.make_signal("NotificationClosed", (id, 2u32)));
+                    Ok(())
+                },
+            );
+            b.method(
+                "RemoveAllNotifications",
+                (),
+                (),
+                |_, state: &mut Arc<Mutex<State>>, ()| {
+                    state.lock().unwrap().calls.push(Call::ClearAll);
+                    Ok(())
+                },
+            );
+            b.method(
+                "DoNotDisturb",
+                (),
+                (),
+                |_, state: &mut Arc<Mutex<State>>, ()| {
+                    let mut state = state.lock().unwrap();
+                    state.calls.push(Call::ToggleDnd);
+                    state.script.do_not_disturb = !state.script.do_not_disturb;
+                    Ok(())
+                },
+            );
+            b.property::<bool, _>("DoNotDisturb")
+                .get(|_, state: &mut Arc<Mutex<State>>| {
+                    let script = &state.lock().unwrap().script;
+                    if script.no_dnd_property {
+                        return Err(MethodErr::no_property("DoNotDisturb"));
+                    }
+                    Ok(script.do_not_disturb)
+                });
+            b.method(
+                "InlineReply",
+                ("id", "text"),
+                (),
+                |_, state: &mut Arc<Mutex<State>>, (id, text): (u32, String)| {
+                    state
+                        .lock()
+                        .unwrap()
+                        .calls
+                        .push(Call::InlineReply(id, text));
+                    Ok(())
+                },
+            );
+            b.method(
+                "InvokeAction",
+                ("id", "action_key"),
+                (),
+                |_, state: &mut Arc<Mutex<State>>, (id, key): (u32, String)| {
+                    state
+                        .lock()
+                        .unwrap()
+                        .calls
+                        .push(Call::InvokeAction(id, key));
+                    Ok(())
+                },
+            );
+            b.method(
+                "GetAllNotifications",
+                (),
+                ("notifications",),
+                move |_, state: &mut Arc<Mutex<State>>, ()| {
+                    let mut state = state.lock().unwrap();
+                    state.calls.push(Call::GetAll);
+                    let notifications: Vec<F> =
+                        state.script.notifications.iter().map(fields).collect();
+                    Ok((notifications,))
+                },
+            );
+            b.method(
+                "ToggleNotificationCenter",
+                (),
+                ("open",),
+                |_, state: &mut Arc<Mutex<State>>, ()| {
+                    let mut state = state.lock().unwrap();
+                    state.calls.push(Call::ToggleNotificationCenter);
+                    state.script.center_open = !state.script.center_open;
+                    Ok((state.script.center_open,))
+                },
+            );
+        });
+        cr.insert(SERVER_PATH, &[token], state.clone());
+        // serves until the test process exits
+        thread::spawn(move || cr.serve(&c));
+        MockServer { state }
+    }
+    pub fn script(&self, change: impl FnOnce(&mut Script)) {
+        change(&mut self.state.lock().unwrap().script);
+    }
+    pub fn calls(&self) -> Vec<Call> {
+        self.state.lock().unwrap().calls.clone()
+    }
+}
+
+fn image_fields(image: &ImageData) -> ImageFields {
+    (
+        image.width,
+        image.height,
+        image.rowstride,
+        image.has_alpha,
+        image.bits_per_sample,
+        image.channels,
+        image.data.clone(),
+    )
+}
+
+fn oxinoti_fields(notification: &Notification) -> OxinotiFields {
+    (
+        notification.app_name.clone(),
+        notification.replaces_id,
+        notification.app_icon.clone(),
+        notification.summary.clone(),
+        notification.body.clone(),
+        notification.actions.clone(),
+        notification.expire_timeout,
+        notification.urgency.to_i32(UrgencyEncoding::Oxinoti),
+        notification.image_path.clone(),
+        notification.progress,
+        image_fields(&notification.image_data),
+    )
+}
+
+fn hinted_fields(notification: &Notification) -> HintedFields {
+    (
+        notification.app_name.clone(),
+        notification.replaces_id,
+        notification.app_icon.clone(),
+        notification.summary.clone(),
+        notification.body.clone(),
+        notification.actions.clone(),
+        notification.expire_timeout,
+        notification.urgency.to_i32(UrgencyEncoding::Spec),
+        notification.image_path.clone(),
+        notification.progress,
+        image_fields(&notification.image_data),
+        notification
+            .hints
+            .iter()
+            .map(|(key, value)| (key.clone(), Variant(value.0.box_clone())))
+            .collect(),
+    )
+}
@@ -0,0 +1,82 @@
+// DBus tests against a private dbus-daemon. Nothing here touches GTK, so
+// they run without a display, only dbus-daemon has to be installed.
+mod bus;
+mod client;
+mod daemon;
+mod listener;
+mod mock;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dbus::arg::{PropMap, Variant};
+use gtk::glib::{self, MainContext, Sender};
+
+use crate::{ImageData, Notification, Urgency};
+
+const WAIT: Duration = Duration::from_secs(5);
+
+// The receiving end of a glib channel, driven by hand instead of a main loop.
+pub struct Received<T> {
+    context: MainContext,
+    items: Rc<RefCell<VecDeque<T>>>,
+}
+
+pub fn channel<T: 'static>() -> (Sender<T>, Received<T>) {
+    let (sender, receiver) = MainContext::channel(glib::PRIORITY_DEFAULT);
+    let context = MainContext::new();
+    let items = Rc::new(RefCell::new(VecDeque::new()));
+    let owner = context.acquire().unwrap();
+    let queue = items.clone();
+    receiver.attach(Some(&context), move |item| {
+        queue.borrow_mut().push_back(item);
+        glib::Continue(true)
+    });
+    drop(owner);
+    (sender, Received { context, items })
+}
+
+impl<T> Received<T> {
+    pub fn next(&self) -> T {
+        let deadline = Instant::now() + WAIT;
+        loop {
+            self.context.iteration(false);
+            if let Some(item) = self.items.borrow_mut().pop_front() {
+                return item;
+            }
+            assert!(Instant::now() < deadline, "nothing arrived in time");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+pub fn notification(id: u32, summary: &str) -> Notification {
+    let mut hints = PropMap::new();
+    hints.insert("timestamp".into(), Variant(Box::new(1_700_000_000i64)));
+    Notification {
+        app_name: "mail".into(),
+        replaces_id: id,
+        app_icon: "mail-unread".into(),
+        summary: summary.into(),
+        body: "body".into(),
+        actions: vec!["default".into(), "Open".into()],
+        expire_timeout: -1,
+        urgency: Urgency::Urgent,
+        image_path: "".into(),
+        progress: -1,
+        image_data: ImageData {
+            width: 1,
+            height: 1,
+            rowstride: 4,
+            has_alpha: true,
+            bits_per_sample: 8,
+            channels: 4,
+            data: vec![1, 2, 3, 4],
+        },
+        hints,
+        received: 0,
+    }
+}
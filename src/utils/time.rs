@@ -0,0 +1,49 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use gtk::glib::DateTime;
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs() as i64)
+}
+
+pub fn start_of_tomorrow() -> i64 {
+    DateTime::now_local()
+        .and_then(|now| {
+            DateTime::from_local(now.year(), now.month(), now.day_of_month(), 0, 0, 0.0)
+        })
+        .and_then(|today| today.add_days(1))
+        .map_or(now() + 24 * 60 * 60, |tomorrow| tomorrow.to_unix())
+}
+
+pub fn relative_time(received: i64) -> String {
+    let (Ok(now), Ok(time)) = (DateTime::now_local(), DateTime::from_unix_local(received)) else {
+        return String::new();
+    };
+    let seconds = now.to_unix() - received;
+    if seconds < 60 {
+        return "just now".into();
+    }
+    if seconds < 60 * 60 {
+        return format!("{} min ago", seconds / 60);
+    }
+    let yesterday = now.add_days(-1).ok().map(|yesterday| yesterday.ymd());
+    let format = if time.ymd() == now.ymd() {
+        "Today %H:%M"
+    } else if yesterday == Some(time.ymd()) {
+        "Yesterday %H:%M"
+    } else if seconds < 7 * 24 * 60 * 60 {
+        "%A %H:%M"
+    } else {
+        "%Y-%m-%d %H:%M"
+    };
+    time.format(format).map(|time| time.to_string()).unwrap_or_default()
+}
+
+pub fn absolute_time(received: i64) -> String {
+    DateTime::from_unix_local(received)
+        .and_then(|time| time.format("%Y-%m-%d %H:%M:%S"))
+        .map(|time| time.to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,192 @@
+use dbus::arg::{
+    Append, AppendAll, Arg, ArgAll, ArgType, Get, Iter, IterAppend, PropMap, ReadAll,
+    TypeMismatchError,
+};
+use dbus::Signature;
+
+use super::time;
+use crate::{ImageData, Notification, Urgency, UrgencyEncoding};
+
+// (iiibiiay): width, height, rowstride, has_alpha, bits_per_sample, channels, data
+type ImageDataFields = (i32, i32, i32, bool, i32, i32, Vec<u8>);
+
+// The fields oxinoti has always sent, the hints dictionary came later.
+type FixedFields = (
+    String,
+    u32,
+    String,
+    String,
+    String,
+    Vec<String>,
+    i32,
+    i32,
+    String,
+    i32,
+    ImageData,
+);
+
+// The notification as oxinoti sends it, either as the arguments of
+// NotificationCenter.Notify or as one struct per entry of GetAllNotifications.
+// Decoding also accepts it without the trailing hints.
+type NotificationFields = (
+    String,
+    u32,
+    String,
+    String,
+    String,
+    Vec<String>,
+    i32,
+    i32,
+    String,
+    i32,
+    ImageData,
+    PropMap,
+);
+
+impl Arg for ImageData {
+    const ARG_TYPE: ArgType = ArgType::Struct;
+    fn signature() -> Signature<'static> {
+        ImageDataFields::signature()
+    }
+}
+
+impl Append for ImageData {
+    fn append_by_ref(&self, ia: &mut IterAppend) {
+        (
+            self.width,
+            self.height,
+            self.rowstride,
+            self.has_alpha,
+            self.bits_per_sample,
+            self.channels,
+            &self.data,
+        )
+            .append_by_ref(ia);
+    }
+}
+
+impl<'a> Get<'a> for ImageData {
+    fn get(i: &mut Iter<'a>) -> Option<Self> {
+        let (width, height, rowstride, has_alpha, bits_per_sample, channels, data) =
+            ImageDataFields::get(i)?;
+        Some(ImageData {
+            width,
+            height,
+            rowstride,
+            has_alpha,
+            bits_per_sample,
+            channels,
+            data,
+        })
+    }
+}
+
+// A notification as it goes over the bus. The urgency stays a plain number
+// until the caller says which encoding the server uses.
+pub struct RawNotification {
+    fields: FixedFields,
+    hints: PropMap,
+}
+
+impl RawNotification {
+    pub fn encode(notification: Notification, encoding: UrgencyEncoding) -> Self {
+        RawNotification {
+            fields: (
+                notification.app_name,
+                notification.replaces_id,
+                notification.app_icon,
+                notification.summary,
+                notification.body,
+                notification.actions,
+                notification.expire_timeout,
+                notification.urgency.to_i32(encoding),
+                notification.image_path,
+                notification.progress,
+                notification.image_data,
+            ),
+            hints: notification.hints,
+        }
+    }
+    pub fn decode(self, encoding: UrgencyEncoding) -> Notification {
+        let (
+            app_name,
+            replaces_id,
+            app_icon,
+            summary,
+            body,
+            actions,
+            expire_timeout,
+            urgency,
+            image_path,
+            progress,
+            image_data,
+        ) = self.fields;
+        let hints = self.hints;
+        // oxinoti may tell us when it received the notification, otherwise it arrived just now
+        let received = hints
+            .get("timestamp")
+            .and_then(|hint| hint.0.as_i64())
+            .unwrap_or_else(time::now);
+        // the urgency hint comes straight from the sending app and always uses spec values
+        let urgency = match hints.get("urgency").and_then(|hint| hint.0.as_i64()) {
+            Some(value) => Urgency::from_i32(value as i32, UrgencyEncoding::Spec),
+            None => Urgency::from_i32(urgency, encoding),
+        };
+        Notification {
+            app_name,
+            replaces_id,
+            app_icon,
+            summary,
+            body,
+            actions,
+            expire_timeout,
+            urgency,
+            image_path,
+            progress,
+            image_data,
+            hints,
+            received,
+        }
+    }
+}
+
+impl Arg for RawNotification {
+    const ARG_TYPE: ArgType = ArgType::Struct;
+    fn signature() -> Signature<'static> {
+        NotificationFields::signature()
+    }
+}
+
+impl Append for RawNotification {
+    fn append_by_ref(&self, ia: &mut IterAppend) {
+        ia.append_struct(|s| {
+            AppendAll::append(&self.fields, s);
+            self.hints.append_by_ref(s);
+        });
+    }
+}
+
+impl<'a> Get<'a> for RawNotification {
+    fn get(i: &mut Iter<'a>) -> Option<Self> {
+        RawNotification::read(&mut i.recurse(ArgType::Struct)?).ok()
+    }
+}
+
+impl ArgAll for RawNotification {
+    type strs = <NotificationFields as ArgAll>::strs;
+    fn strs_sig<F: FnMut(&'static str, Signature<'static>)>(names: Self::strs, f: F) {
+        NotificationFields::strs_sig(names, f);
+    }
+}
+
+impl ReadAll for RawNotification {
+    fn read(i: &mut Iter) -> Result<Self, TypeMismatchError> {
+        let fields = FixedFields::read(i)?;
+        // only a missing dictionary is fine, anything else in its place is not
+        let hints = match i.arg_type() {
+            ArgType::Invalid => PropMap::new(),
+            _ => i.read()?,
+        };
+        Ok(RawNotification { fields, hints })
+    }
+}
@@ -1,24 +1,28 @@
-use std::cell::{Cell, RefCell};
+use std::cell::{Cell, OnceCell, RefCell};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::thread;
-use std::time::Duration;
 
+use crate::groupbox::GroupBox;
 use crate::notibox::NotiBox;
-use crate::utils::NotificationButton;
+use crate::utils::image::ImageDataError;
+use crate::utils::client::Client;
+use crate::utils::config::Config;
+use crate::utils::listener::CenterProperties;
+use crate::utils::sort::SortMode;
+use crate::utils::{markup, time, NotificationButton};
 use adw::subclass::prelude::AdwApplicationWindowImpl;
-use dbus::blocking::Connection;
 use glib::subclass::InitializingObject;
 use gtk::gdk_pixbuf::{self, Pixbuf};
 use gtk::glib::clone;
 use gtk::subclass::prelude::*;
 use gtk::{
-    glib, Button, CompositeTemplate, Entry, Image, Label, PolicyType, ProgressBar, ScrolledWindow,
+    gio, glib, Button, CompositeTemplate, DropDown, Entry, Image, Label, MenuButton, PolicyType,
+    ProgressBar, ScrolledWindow, ToggleButton, Widget,
 };
 use gtk::{prelude::*, Box};
 
-use crate::{ImageData, Notification};
+use crate::{ImageData, Notification, Urgency};
 
 #[derive(CompositeTemplate, Default)]
 #[template(resource = "/org/dashie/oxidash/window.ui")]
@@ -26,7 +30,9 @@ pub struct Window {
     #[template_child]
     pub mainbox: TemplateChild<Box>,
     #[template_child]
-    pub button: TemplateChild<Button>,
+    pub button: TemplateChild<ToggleButton>,
+    #[template_child]
+    pub dnd_menu_button: TemplateChild<MenuButton>,
     #[template_child]
     pub exit_button: TemplateChild<Button>,
     #[template_child]
@@ -35,10 +41,66 @@ pub struct Window {
     pub notibox: TemplateChild<Box>,
     #[template_child]
     pub scrolled_window: TemplateChild<ScrolledWindow>,
+    #[template_child]
+    pub group_button: TemplateChild<ToggleButton>,
+    #[template_child]
+    pub sort_dropdown: TemplateChild<DropDown>,
+    #[template_child]
+    pub pinned_box: TemplateChild<Box>,
+    #[template_child]
+    pub pinned_confirmation: TemplateChild<Box>,
+    #[template_child]
+    pub pinned_confirmation_label: TemplateChild<Label>,
+    #[template_child]
+    pub keep_pinned_button: TemplateChild<Button>,
+    #[template_child]
+    pub clear_pinned_button: TemplateChild<Button>,
+    #[template_child]
+    pub server_banner: TemplateChild<Label>,
+    #[template_child]
+    pub error_banner: TemplateChild<Box>,
+    #[template_child]
+    pub error_label: TemplateChild<Label>,
+    #[template_child]
+    pub dismiss_error_button: TemplateChild<Button>,
     pub has_pointer: Cell<bool>,
+    pub grouped: Cell<bool>,
+    pub sort_mode: Cell<SortMode>,
+    pub groups: RefCell<HashMap<String, GroupBox>>,
+    pub client: OnceCell<Client>,
+    pub properties: OnceCell<CenterProperties>,
+    pub standalone: Cell<bool>,
+    pub resident: Cell<bool>,
+    pub config: RefCell<Config>,
+    // style.css and config.json, reloaded when they change
+    pub monitors: RefCell<Vec<gio::FileMonitor>>,
+    // selected with the keyboard, highlighted with the focused class
+    pub focused: RefCell<Option<NotiBox>>,
+    // the file the error banner complains about, if it is about a file
+    pub error_file: RefCell<Option<PathBuf>>,
 }
 
 impl Window {
+    pub fn client(&self) -> &Client {
+        self.client.get().expect("client is set in build_ui")
+    }
+    pub fn show_error(&self, message: &str) {
+        eprintln!("{}", message);
+        self.error_label.set_text(message);
+        self.error_banner.show();
+        self.error_file.take();
+    }
+    pub fn show_file_error(&self, path: &Path, message: &str) {
+        self.show_error(message);
+        self.error_file.replace(Some(path.to_path_buf()));
+    }
+    // hides the error banner once the file it complains about loaded fine
+    pub fn file_loaded(&self, path: &Path) {
+        if self.error_file.borrow().as_deref() == Some(path) {
+            self.error_file.take();
+            self.error_banner.hide();
+        }
+    }
     fn delete_specific_notification(
         &self,
         button: &NotificationButton,
@@ -47,26 +109,44 @@ impl Window {
         let id = button.imp().notification_id.get();
         let mut map = id_map.borrow_mut();
         map.remove(&id);
-        thread::spawn(move || {
-            let conn = Connection::new_session().unwrap();
-            let proxy = conn.with_proxy(
-                "org.freedesktop.Notifications",
-                "/org/freedesktop/Notifications",
-                Duration::from_millis(1000),
-            );
-            let _: Result<(), dbus::Error> =
-                proxy.method_call("org.freedesktop.Notifications", "CloseNotification", (id,));
-        });
-        self.notibox.remove(&*button.imp().notibox.take());
-        self.notibox.remove(button);
+        self.client().dismiss(id);
+        self.detach_notification(&button.imp().notibox.take());
         if map.is_empty() {
             self.scrolled_window.hide();
         }
+        self.update_counts(&map);
+    }
+    pub fn update_counts(&self, map: &HashMap<u32, Rc<NotificationButton>>) {
+        let Some(properties) = self.properties.get() else {
+            return;
+        };
+        let urgent_count = map
+            .values()
+            .filter(|button| {
+                *button.imp().notibox.borrow().imp().urgency.borrow() == Urgency::Urgent
+            })
+            .count();
+        properties.update(|state| {
+            state.count = map.len() as u32;
+            state.urgent_count = urgent_count as u32;
+        });
     }
     fn delete_specific_notification_with_id(
         &self,
         id: u32,
         id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>,
+    ) {
+        if !id_map.borrow().contains_key(&id) {
+            return;
+        }
+        self.client().dismiss(id);
+        self.remove_notification_with_id(id, id_map);
+    }
+    // only updates the dashboard, for notifications the server already closed
+    pub fn remove_notification_with_id(
+        &self,
+        id: u32,
+        id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>,
     ) {
         let mut map = id_map.borrow_mut();
         let button = map.get(&id);
@@ -74,22 +154,285 @@ impl Window {
             return;
         }
         let button = button.unwrap();
-        thread::spawn(move || {
-            let conn = Connection::new_session().unwrap();
-            let proxy = conn.with_proxy(
-                "org.freedesktop.Notifications",
-                "/org/freedesktop/Notifications",
-                Duration::from_millis(1000),
-            );
-            let _: Result<(), dbus::Error> =
-                proxy.method_call("org.freedesktop.Notifications", "CloseNotification", (id,));
-        });
-        self.notibox.remove(&*button.imp().notibox.take());
-        self.notibox.remove(&*button.clone());
+        self.detach_notification(&button.imp().notibox.take());
         map.remove(&id);
         if map.is_empty() {
             self.scrolled_window.hide();
         }
+        self.update_counts(&map);
+    }
+    fn attach_notification(
+        &self,
+        notibox: &NotiBox,
+        id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>,
+    ) {
+        if notibox.imp().pinned.get() {
+            self.pinned_box.append(notibox);
+            self.pinned_box.show();
+            return;
+        }
+        if !self.grouped.get() {
+            self.notibox.append(notibox);
+            return;
+        }
+        let key = notibox.imp().group.borrow().clone();
+        let existing = self.groups.borrow().get(&key).cloned();
+        let group = existing.unwrap_or_else(|| {
+            let group = self.create_group(notibox, id_map);
+            self.groups.borrow_mut().insert(key, group.clone());
+            group
+        });
+        group.imp().list.borrow().append(notibox);
+        update_group(&group);
+    }
+    fn detach_notification(&self, notibox: &NotiBox) {
+        if self.focused.borrow().as_ref() == Some(notibox) {
+            self.focus_notification(None);
+        }
+        if let Some(parent) = notibox.parent().and_downcast::<Box>() {
+            parent.remove(notibox);
+        }
+        if self.pinned_box.first_child().is_none() {
+            self.pinned_box.hide();
+        }
+        let key = notibox.imp().group.borrow().clone();
+        let mut groups = self.groups.borrow_mut();
+        let Some(group) = groups.get(&key) else {
+            return;
+        };
+        if group.imp().list.borrow().first_child().is_none() {
+            self.notibox.remove(group);
+            groups.remove(&key);
+        } else {
+            update_group(group);
+        }
+    }
+    pub fn clear_notifications(
+        &self,
+        include_pinned: bool,
+        id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>,
+    ) {
+        let mut pinned = Vec::new();
+        let mut unpinned = Vec::new();
+        for (id, button) in id_map.borrow().iter() {
+            if button.imp().notibox.borrow().imp().pinned.get() {
+                pinned.push(*id);
+            } else {
+                unpinned.push(*id);
+            }
+        }
+        if include_pinned || pinned.is_empty() {
+            self.client().clear_all();
+            self.remove_all_notifications(id_map);
+            return;
+        }
+        for id in unpinned {
+            self.delete_specific_notification_with_id(id, id_map.clone());
+        }
+        self.pinned_confirmation_label.set_text(&format!(
+            "Also clear {} pinned notification{}?",
+            pinned.len(),
+            if pinned.len() == 1 { "" } else { "s" }
+        ));
+        self.pinned_confirmation.show();
+    }
+    pub fn remove_all_notifications(
+        &self,
+        id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>,
+    ) {
+        let ids: Vec<u32> = id_map.borrow().keys().copied().collect();
+        for id in ids {
+            self.remove_notification_with_id(id, id_map.clone());
+        }
+        self.pinned_confirmation.hide();
+    }
+    // in the order they are shown, without the hidden ones of collapsed groups
+    fn shown_notifications(&self) -> Vec<NotiBox> {
+        let mut notiboxes = Vec::new();
+        for container in [&*self.pinned_box, &*self.notibox] {
+            let mut child = container.first_child();
+            while let Some(widget) = child {
+                child = widget.next_sibling();
+                match widget.downcast::<GroupBox>() {
+                    Ok(group) => notiboxes.extend(group_notifications(&group)),
+                    Err(widget) => notiboxes.extend(widget.downcast::<NotiBox>().ok()),
+                }
+            }
+        }
+        notiboxes.retain(|notibox| notibox.is_visible());
+        notiboxes
+    }
+    fn focus_notification(&self, notibox: Option<&NotiBox>) {
+        if let Some(previous) = self.focused.replace(notibox.cloned()) {
+            previous.remove_css_class("focused");
+        }
+        if let Some(notibox) = notibox {
+            notibox.add_css_class("focused");
+            self.scroll_to(notibox);
+        }
+    }
+    // the notification after a removed one takes over its focus
+    fn focus_at(&self, index: usize) {
+        let shown = self.shown_notifications();
+        self.focus_notification(shown.get(index).or(shown.last()));
+    }
+    fn focused_index(&self) -> Option<usize> {
+        let focused = self.focused.borrow();
+        let focused = focused.as_ref()?;
+        self.shown_notifications()
+            .iter()
+            .position(|notibox| notibox == focused)
+    }
+    fn scroll_to(&self, notibox: &NotiBox) {
+        let Some(bounds) = notibox.compute_bounds(&*self.scrolled_window) else {
+            return;
+        };
+        let adjustment = self.scrolled_window.vadjustment();
+        let top = bounds.y() as f64;
+        let bottom = (bounds.y() + bounds.height()) as f64;
+        if top < 0.0 {
+            adjustment.set_value(adjustment.value() + top);
+        } else if bottom > adjustment.page_size() {
+            adjustment.set_value(adjustment.value() + bottom - adjustment.page_size());
+        }
+    }
+    pub fn move_focus(&self, forward: bool) {
+        let shown = self.shown_notifications();
+        let last = shown.len().saturating_sub(1);
+        let index = match (self.focused_index(), forward) {
+            (Some(index), true) => (index + 1).min(last),
+            (Some(index), false) => index.saturating_sub(1),
+            (None, true) => 0,
+            (None, false) => last,
+        };
+        self.focus_notification(shown.get(index));
+    }
+    pub fn dismiss_focused(&self, id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>) {
+        let Some(index) = self.focused_index() else {
+            return;
+        };
+        let id = self.focused.borrow().as_ref().unwrap().imp().id.get();
+        self.delete_specific_notification_with_id(id, id_map);
+        self.focus_at(index);
+    }
+    pub fn activate_focused(&self, id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>) {
+        let Some(index) = self.focused_index() else {
+            return;
+        };
+        let focused = self.focused.borrow().clone().unwrap();
+        let notiimp = focused.imp();
+        if !notiimp.has_default_action.get() {
+            return;
+        }
+        let (id, resident) = (notiimp.id.get(), notiimp.resident.get());
+        invoke_action(id, "default".to_string(), resident, self, id_map);
+        if self.focused.borrow().is_none() {
+            self.focus_at(index);
+        }
+    }
+    pub fn expand_focused(&self) {
+        let Some(focused) = self.focused.borrow().clone() else {
+            return;
+        };
+        // pinned ones are never in a group
+        if focused.imp().pinned.get() {
+            return;
+        }
+        let key = focused.imp().group.borrow().clone();
+        let Some(group) = self.groups.borrow().get(&key).cloned() else {
+            return;
+        };
+        group.imp().collapse_button.borrow().emit_clicked();
+        // collapsing only leaves the newest one visible
+        if !focused.is_visible() {
+            let newest = group_notifications(&group)
+                .into_iter()
+                .find(|notibox| notibox.is_visible());
+            self.focus_notification(newest.as_ref());
+        }
+    }
+    pub fn reply_to_focused(&self) {
+        let Some(focused) = self.focused.borrow().clone() else {
+            return;
+        };
+        let notiimp = focused.imp();
+        if notiimp.has_inline_reply.get() {
+            notiimp.inline_reply.borrow().grab_focus();
+        }
+    }
+    fn create_group(
+        &self,
+        notibox: &NotiBox,
+        id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>,
+    ) -> GroupBox {
+        let notiimp = notibox.imp();
+        let key = notiimp.group.borrow().clone();
+        let app_icon = notiimp.app_icon.borrow().clone();
+        let app_info = gio::DesktopAppInfo::new(&format!("{}.desktop", key));
+
+        let group = GroupBox::new(gtk::Orientation::Vertical, 5);
+        let groupimp = group.imp();
+        group.set_widget_name("NotificationGroup");
+        group.set_css_classes(&["NotificationGroup"]);
+        *groupimp.key.borrow_mut() = key;
+
+        let header = Box::new(gtk::Orientation::Horizontal, 5);
+        header.set_css_classes(&["NotificationGroupHeader"]);
+        let icon = Image::new();
+        icon.set_css_classes(&["NotificationGroupIcon"]);
+        icon.set_pixel_size(24);
+        if let Some(gicon) = app_info.as_ref().and_then(|info| info.icon()) {
+            icon.set_from_gicon(&gicon);
+        } else if Path::new(&app_icon).is_file() {
+            icon.set_from_file(Some(&app_icon));
+        } else {
+            icon.set_icon_name(Some(&app_icon));
+        }
+        let name = match app_info {
+            Some(info) => info.display_name().to_string(),
+            None => notiimp.app_name.borrow().clone(),
+        };
+        let name = Label::new(Some(&name));
+        name.set_css_classes(&["NotificationGroupName"]);
+        name.set_xalign(0.0);
+        name.set_hexpand(true);
+        let count = Label::new(None);
+        count.set_css_classes(&["NotificationGroupCount"]);
+
+        let collapse_button = Button::from_icon_name("pan-down-symbolic");
+        collapse_button.set_css_classes(&["NotificationGroupCollapse"]);
+        collapse_button.connect_clicked(clone!(@weak group => move |_| {
+            let collapsed = &group.imp().collapsed;
+            collapsed.set(!collapsed.get());
+            update_group(&group);
+        }));
+        let clear_button = Button::from_icon_name("small-x-symbolic");
+        clear_button.set_css_classes(&["NotificationGroupClear"]);
+        clear_button.set_tooltip_text(Some("Clear group"));
+        clear_button.connect_clicked(clone!(@weak self as window, @weak group, @weak id_map => move |_| {
+            for notibox in group_notifications(&group) {
+                window.delete_specific_notification_with_id(notibox.imp().id.get(), id_map.clone());
+            }
+        }));
+
+        header.append(&icon);
+        header.append(&name);
+        header.append(&count);
+        header.append(&collapse_button);
+        header.append(&clear_button);
+        let list = Box::new(gtk::Orientation::Vertical, 6);
+        list.set_css_classes(&["NotificationGroupList"]);
+        group.append(&header);
+        group.append(&list);
+
+        *groupimp.icon.borrow_mut() = icon;
+        *groupimp.name.borrow_mut() = name;
+        *groupimp.count.borrow_mut() = count;
+        *groupimp.collapse_button.borrow_mut() = collapse_button;
+        *groupimp.clear_button.borrow_mut() = clear_button;
+        *groupimp.list.borrow_mut() = list;
+        self.notibox.append(&group);
+        group
     }
 }
 
@@ -124,18 +467,19 @@ pub fn modify_notification(
     let picbuttonbox = notibox.picbuttonbox.borrow_mut();
 
     let exists = notibox.has_progbar.get();
-    if notification.progress < 0 && exists {
-        basebox.remove(&notibox.progbar.take());
+    let progress = notification.current_progress();
+    if progress < 0 && exists {
+        notibox_borrow.remove(&notibox.progbar.take());
         notibox.has_progbar.set(false);
-    } else if notification.progress > 0 {
+    } else if progress > 0 {
         let mut progbar = notibox.progbar.borrow_mut();
         if !exists {
             let newprog = ProgressBar::new();
             *progbar = newprog;
-            basebox.append(&*progbar);
+            notibox_borrow.insert_child_after(&*progbar, Some(&*basebox));
             notibox.has_progbar.set(true);
         }
-        progbar.set_fraction(notification.progress as f64 / 100.0);
+        progbar.set_fraction(progress as f64 / 100.0);
     }
 
     let exists = notibox.has_summary.get();
@@ -143,15 +487,15 @@ pub fn modify_notification(
         textbox.remove(&notibox.summary.take());
         notibox.has_summary.set(false);
     } else if notification.summary != "" {
-        let (text, css_classes, _) = class_from_html(notification.summary);
+        let summary = markup::parse(&notification.summary);
         let mut text_borrow = notibox.summary.borrow_mut();
         if !exists {
             *text_borrow = Label::new(None);
             textbox.append(&*text_borrow);
             notibox.has_summary.set(true);
         }
-        text_borrow.set_text(text.as_str());
-        text_borrow.set_css_classes(&[&"summary", &css_classes]);
+        text_borrow.set_markup(&summary.markup);
+        text_borrow.set_css_classes(&["summary"]);
     }
 
     let mut has_body_image = false;
@@ -162,20 +506,18 @@ pub fn modify_notification(
         textbox.remove(&notibox.body.take());
         notibox.has_body.set(false);
     } else if notification.body != "" {
-        let (text, css_classes, has_image) = class_from_html(notification.body);
+        let body = markup::parse(&notification.body);
         let mut text_borrow = notibox.body.borrow_mut();
-        if has_image {
-            has_body_image = has_image;
-            image_path = css_classes;
-        } else {
-            text_borrow.style_context().add_class(&css_classes);
+        if let Some(path) = body.image_path() {
+            has_body_image = true;
+            image_path = path.to_string();
         }
         if !exists {
             *text_borrow = Label::new(None);
             textbox.append(&*text_borrow);
             notibox.has_body.set(true);
         }
-        text_borrow.set_text(text.as_str());
+        set_body(&text_borrow, &body);
         text_borrow.set_css_classes(&[&"text"]);
     }
 
@@ -196,18 +538,15 @@ pub fn modify_notification(
             &notification.image_path,
             &notification.app_icon,
             &image_borrow,
+            window.config.borrow().layout.image_size,
         );
     }
 
-    let mut has_inline_reply = false;
-    for action in notification.actions.iter() {
-        if action == "inline-reply" {
-            has_inline_reply = true;
-        }
-    }
+    let actions = notification.parsed_actions();
+    let has_inline_reply = actions.iter().any(|action| action.is_inline_reply());
     let exists = notibox.has_inline_reply.get();
     if !has_inline_reply && exists {
-        basebox.remove(&notibox.inline_reply.take());
+        notibox_borrow.remove(&notibox.inline_reply.take());
         notibox.has_inline_reply.set(false);
     } else if has_inline_reply {
         let mut entry = notibox.inline_reply.borrow_mut();
@@ -220,21 +559,27 @@ pub fn modify_notification(
                 activate_inline_reply(id, text, &window, id_map_clone.clone());
             }));
             *entry = newentry;
-            basebox.append(&*entry);
+            if notibox.has_progbar.get() {
+                notibox_borrow.insert_child_after(&*entry, Some(&*notibox.progbar.borrow()));
+            } else {
+                notibox_borrow.insert_child_after(&*entry, Some(&*basebox));
+            }
             notibox.has_inline_reply.set(true);
         }
     }
 
     let exists = notibox.has_body_image.get();
     if !has_body_image && exists {
-        basebox.remove(&notibox.body_image.take());
+        notibox_borrow.remove(&notibox.body_image.take());
         notibox.has_body_image.set(false);
     } else if has_body_image {
         let mut image_borrow = notibox.body_image.borrow_mut();
         if !exists {
             let image = Image::new();
+            image.set_size_request(500, 500);
             *image_borrow = image;
-            basebox.append(&*image_borrow);
+            let sibling = notibox.action_box.borrow().prev_sibling();
+            notibox_borrow.insert_child_after(&*image_borrow, sibling.as_ref());
             notibox.has_body_image.set(true);
         }
         set_image(
@@ -242,8 +587,33 @@ pub fn modify_notification(
             &image_path,
             &notification.app_icon,
             &image_borrow,
+            window.config.borrow().layout.image_size,
         );
     }
+
+    set_actions(notibox, &notification, window, id_map.clone());
+    set_category(notibox, notification.category());
+    notibox.received.set(notification.received);
+    set_time(notibox);
+    for urgency in [Urgency::Low, Urgency::Normal, Urgency::Urgent] {
+        notibox_borrow.remove_css_class(urgency.to_str());
+    }
+    notibox_borrow.add_css_class(notification.urgency.to_str());
+    let pinned = notification.urgency == Urgency::Urgent;
+    *notibox.urgency.borrow_mut() = notification.urgency.clone();
+    if notibox.pinned.get() != pinned {
+        window.detach_notification(&notibox_borrow);
+        notibox.pinned.set(pinned);
+        window.attach_notification(&notibox_borrow, id_map.clone());
+    }
+    if let Some(group) = notibox_borrow
+        .parent()
+        .and_then(|list| list.parent())
+        .and_downcast::<GroupBox>()
+    {
+        update_group(&group);
+    }
+    sort_notifications(window);
 }
 
 pub fn show_notification(
@@ -251,10 +621,19 @@ pub fn show_notification(
     window: &Window,
     id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>,
 ) {
+    if notification.transient() {
+        return;
+    }
     let notibox = Rc::new(NotiBox::new(gtk::Orientation::Vertical, 5));
     let notiimp = notibox.imp();
+    notiimp.id.set(notification.replaces_id);
+    *notiimp.group.borrow_mut() = group_key(notification);
+    *notiimp.app_name.borrow_mut() = notification.app_name.clone();
+    *notiimp.app_icon.borrow_mut() = notification.app_icon.clone();
+    notiimp.pinned.set(notification.urgency == Urgency::Urgent);
+    *notiimp.urgency.borrow_mut() = notification.urgency.clone();
     notibox.set_widget_name("Notification");
-    notibox.set_css_classes(&["Notification"]);
+    notibox.set_css_classes(&["Notification", notification.urgency.to_str()]);
     let basebox = Box::new(gtk::Orientation::Horizontal, 5);
     basebox.set_css_classes(&["BaseBox"]);
     basebox.set_halign(gtk::Align::Fill);
@@ -264,29 +643,25 @@ pub fn show_notification(
     textbox.set_halign(gtk::Align::Fill);
     let picbuttonbox = Box::new(gtk::Orientation::Horizontal, 5);
     picbuttonbox.set_css_classes(&["PictureButtonBox"]);
-    picbuttonbox.set_size_request(100, 110);
+    let image_size = window.config.borrow().layout.image_size;
+    picbuttonbox.set_size_request(image_size, image_size + 10);
     picbuttonbox.set_halign(gtk::Align::End);
     picbuttonbox.set_hexpand(false);
 
-    let mut has_inline_reply = false;
-    for action in notification.actions.iter() {
-        if action == "inline-reply" {
-            has_inline_reply = true;
-        }
-    }
+    let actions = notification.parsed_actions();
+    let has_inline_reply = actions.iter().any(|action| action.is_inline_reply());
 
     let mut has_body_image = false;
     let mut image_path = "".to_string();
 
     if notification.body != "" {
         notiimp.has_body.set(true);
-        let (textstr, css_classes, has_image) = class_from_html(notification.body.clone());
-        let text = Label::new(Some(&textstr));
-        if has_image {
-            has_body_image = has_image;
-            image_path = css_classes;
-        } else {
-            text.style_context().add_class(&css_classes);
+        let body = markup::parse(&notification.body);
+        let text = Label::new(None);
+        set_body(&text, &body);
+        if let Some(path) = body.image_path() {
+            has_body_image = true;
+            image_path = path.to_string();
         }
         text.set_css_classes(&["text"]);
         text.set_xalign(0.0);
@@ -298,9 +673,9 @@ pub fn show_notification(
     }
     if notification.summary != "" {
         notiimp.has_summary.set(true);
-        let (textstr, css_classes, _) = class_from_html(notification.summary.clone());
-        let summary = Label::new(Some(&textstr));
-        summary.set_css_classes(&["summary", &css_classes]);
+        let summary = Label::new(None);
+        summary.set_markup(&markup::parse(&notification.summary).markup);
+        summary.set_css_classes(&["summary"]);
         summary.set_xalign(0.0);
         summary.set_wrap(true);
         summary.set_halign(gtk::Align::Center);
@@ -309,23 +684,31 @@ pub fn show_notification(
         textbox.append(&*shared_summary);
     }
     if notification.app_name != "" {
-        let (textstr, css_classes, _) = class_from_html(notification.app_name.clone());
-        let appname = Label::new(Some(&textstr));
-        appname.set_css_classes(&["app_name", &css_classes]);
+        let appname = Label::new(Some(&notification.app_name));
+        appname.set_css_classes(&["app_name"]);
         appname.set_xalign(0.0);
         appname.set_wrap(true);
         appname.set_halign(gtk::Align::Center);
         textbox.append(&appname);
     }
+    let time = Label::new(None);
+    time.set_css_classes(&["time"]);
+    time.set_xalign(0.0);
+    time.set_halign(gtk::Align::Center);
+    textbox.append(&time);
+    *notiimp.time.borrow_mut() = time;
+    notiimp.received.set(notification.received);
+    set_time(notiimp);
     basebox.append(&textbox);
 
     let image = Image::new();
-    image.set_size_request(100, 100);
+    image.set_size_request(image_size, image_size);
     notiimp.has_image.set(set_image(
         &notification.image_data,
         &notification.image_path,
         &notification.app_icon,
         &image,
+        image_size,
     ));
     let mut shared_image = notiimp.image.borrow_mut();
     *shared_image = image;
@@ -333,9 +716,10 @@ pub fn show_notification(
 
     notibox.append(&basebox);
     let progbar = ProgressBar::new();
-    if notification.progress > -1 {
+    let progress = notification.current_progress();
+    if progress > -1 {
         notiimp.has_progbar.set(true);
-        progbar.set_fraction(notification.progress as f64 / 100.0);
+        progbar.set_fraction(progress as f64 / 100.0);
         let mut shared_progbar = notiimp.progbar.borrow_mut();
         *shared_progbar = progbar;
         notibox.append(&*shared_progbar);
@@ -343,29 +727,53 @@ pub fn show_notification(
 
     let inline_reply = Entry::new();
     if has_inline_reply {
+        notiimp.has_inline_reply.set(true);
         let id = notification.replaces_id;
-        let text = inline_reply.text().to_string();
-        inline_reply.connect_activate(clone!(@weak window, @weak id_map => move |_| {
-            activate_inline_reply(id, text.clone(), &window, id_map);
+        inline_reply.connect_activate(clone!(@weak window, @weak id_map => move |entry| {
+            let text = entry.text().to_string();
+            activate_inline_reply(id, text, &window, id_map);
         }));
-        notibox.append(&inline_reply);
+        let mut shared_reply = notiimp.inline_reply.borrow_mut();
+        *shared_reply = inline_reply;
+        notibox.append(&*shared_reply);
     }
 
     if has_body_image {
         let body_image = Image::new();
         body_image.set_size_request(500, 500);
-        println!("{}", image_path);
         notiimp.has_body_image.set(set_image(
             &notification.image_data,
             &image_path,
             &notification.app_icon,
             &body_image,
+            image_size,
         ));
         let mut shared_image = notiimp.body_image.borrow_mut();
         *shared_image = body_image;
         notibox.append(&*shared_image);
     }
 
+    let action_box = Box::new(gtk::Orientation::Horizontal, 5);
+    action_box.set_css_classes(&["ActionBox"]);
+    action_box.set_homogeneous(true);
+    notibox.append(&action_box);
+    *notiimp.action_box.borrow_mut() = action_box;
+    set_actions(notiimp, notification, window, id_map.clone());
+    set_category(notiimp, notification.category());
+
+    let id = notification.replaces_id;
+    let default_action = gtk::GestureClick::new();
+    default_action.set_button(gtk::gdk::ffi::GDK_BUTTON_PRIMARY as u32);
+    default_action.connect_released(clone!(@weak window, @weak id_map => move |gesture, _, _, _| {
+        let notibox = gesture.widget().downcast::<NotiBox>().unwrap();
+        let notiimp = notibox.imp();
+        if notiimp.has_default_action.get() {
+            let resident = notiimp.resident.get();
+            invoke_action(id, "default".to_string(), resident, &window, id_map);
+        }
+    }));
+    notibox.add_controller(default_action);
+
     let buttonbox = Box::new(gtk::Orientation::Horizontal, 0);
     buttonbox.set_css_classes(&["CloseNotificationButton"]);
     buttonbox.set_size_request(60, 100);
@@ -386,10 +794,13 @@ pub fn show_notification(
 
     picbuttonbox.append(&buttonbox);
     basebox.append(&picbuttonbox);
-    window.notibox.append(&*notibox);
+    *notiimp.basebox.borrow_mut() = basebox;
+    *notiimp.textbox.borrow_mut() = textbox;
+    *notiimp.picbuttonbox.borrow_mut() = picbuttonbox;
+    window.attach_notification(&notibox, id_map.clone());
+    sort_notifications(window);
     let notibutton = Rc::new(button);
-    let mut notibox_borrow = notibutton.imp().notibox.borrow_mut();
-    *notibox_borrow = notibox.clone();
+    *notibutton.imp().notibox.borrow_mut() = notibox.clone();
     buttonbox.append(&*notibutton);
     if !window.scrolled_window.is_visible() {
         window.scrolled_window.show();
@@ -397,11 +808,13 @@ pub fn show_notification(
     id_map
         .borrow_mut()
         .insert(notification.replaces_id, notibutton.clone());
+    window.update_counts(&id_map.borrow());
 }
 
 pub fn resize_window(window: &crate::Window) {
-    if window.height() >= 1000 {
-        window.set_height_request(1000);
+    let max_height = window.imp().config.borrow().layout.max_height;
+    if window.height() >= max_height {
+        window.set_height_request(max_height);
         window.set_vexpand(false);
         window
             .imp()
@@ -417,6 +830,119 @@ pub fn resize_window(window: &crate::Window) {
     window.queue_resize();
 }
 
+pub fn set_grouping(
+    window: &Window,
+    grouped: bool,
+    id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>,
+) {
+    window.grouped.set(grouped);
+    let mut notiboxes: Vec<NotiBox> = id_map
+        .borrow()
+        .values()
+        .map(|button| NotiBox::clone(&button.imp().notibox.borrow()))
+        .collect();
+    notiboxes.sort_by_key(|notibox| notibox.imp().received.get());
+    for notibox in notiboxes.iter() {
+        if let Some(parent) = notibox.parent().and_downcast::<Box>() {
+            parent.remove(notibox);
+        }
+        notibox.set_visible(true);
+    }
+    for (_, group) in window.groups.take() {
+        window.notibox.remove(&group);
+    }
+    for notibox in notiboxes.iter() {
+        window.attach_notification(notibox, id_map.clone());
+    }
+    sort_notifications(window);
+}
+
+pub fn sort_notifications(window: &Window) {
+    let mode = window.sort_mode.get();
+    for group in window.groups.borrow().values() {
+        sort_children(&group.imp().list.borrow(), mode);
+    }
+    sort_children(&window.pinned_box, mode);
+    sort_children(&window.notibox, mode);
+}
+
+fn sort_children(container: &Box, mode: SortMode) {
+    // groups are ordered by their first notification, so their lists have to be sorted first
+    let first_notification = |widget: &Widget| match widget.downcast_ref::<GroupBox>() {
+        Some(group) => group.imp().list.borrow().first_child().and_downcast::<NotiBox>(),
+        None => widget.clone().downcast::<NotiBox>().ok(),
+    };
+    let mut children = Vec::new();
+    let mut child = container.first_child();
+    while let Some(widget) = child {
+        child = widget.next_sibling();
+        children.push(widget);
+    }
+    children.sort_by(|a, b| {
+        match (first_notification(a), first_notification(b)) {
+            (Some(a), Some(b)) => mode.compare(&a, &b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        }
+    });
+    let mut previous: Option<Widget> = None;
+    for widget in children {
+        container.reorder_child_after(&widget, previous.as_ref());
+        previous = Some(widget);
+    }
+}
+
+fn group_key(notification: &Notification) -> String {
+    notification
+        .desktop_entry()
+        .unwrap_or(&notification.app_name)
+        .to_string()
+}
+
+fn group_notifications(group: &GroupBox) -> Vec<NotiBox> {
+    let mut notiboxes = Vec::new();
+    let mut child = group.imp().list.borrow().first_child();
+    while let Some(widget) = child {
+        child = widget.next_sibling();
+        if let Ok(notibox) = widget.downcast::<NotiBox>() {
+            notiboxes.push(notibox);
+        }
+    }
+    notiboxes
+}
+
+fn update_group(group: &GroupBox) {
+    let groupimp = group.imp();
+    let notiboxes = group_notifications(group);
+    let collapsed = groupimp.collapsed.get();
+    let newest = notiboxes
+        .iter()
+        .max_by_key(|notibox| notibox.imp().received.get())
+        .cloned();
+    for notibox in notiboxes.iter() {
+        notibox.set_visible(!collapsed || Some(notibox) == newest.as_ref());
+    }
+    groupimp.count.borrow().set_text(&notiboxes.len().to_string());
+    if collapsed {
+        groupimp
+            .collapse_button
+            .borrow()
+            .set_icon_name("pan-end-symbolic");
+        group.add_css_class("collapsed");
+    } else {
+        groupimp
+            .collapse_button
+            .borrow()
+            .set_icon_name("pan-down-symbolic");
+        group.remove_css_class("collapsed");
+    }
+}
+
+pub fn refresh_times(id_map: &HashMap<u32, Rc<NotificationButton>>) {
+    for button in id_map.values() {
+        set_time(button.imp().notibox.borrow().imp());
+    }
+}
+
 pub fn check_duplicates(
     notification: &Notification,
     id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>,
@@ -464,6 +990,35 @@ impl ObjectImpl for Window {
                 .expect("Could not close application");
         });
 
+        self.sort_dropdown
+            .connect_selected_notify(clone!(@weak self as window => move |dropdown| {
+                let mode = SortMode::from_index(dropdown.selected()).unwrap_or_default();
+                window.sort_mode.set(mode);
+                sort_notifications(&window);
+            }));
+
+        self.group_button.connect_toggled(move |button| {
+            button
+                .activate_action("win.toggle_grouping", None)
+                .expect("wat");
+        });
+
+        self.dismiss_error_button
+            .connect_clicked(clone!(@weak self as window => move |_| {
+                window.error_banner.hide();
+            }));
+
+        self.keep_pinned_button
+            .connect_clicked(clone!(@weak self as window => move |_| {
+                window.pinned_confirmation.hide();
+            }));
+
+        self.clear_pinned_button.connect_clicked(move |button| {
+            button
+                .activate_action("win.delete_pinned_notifications", None)
+                .expect("wat");
+        });
+
         self.clear_history_button.connect_clicked(move |button| {
             button
                 .activate_action("win.delete_notifications", None)
@@ -480,40 +1035,93 @@ impl AdwApplicationWindowImpl for Window {}
 
 impl ApplicationWindowImpl for Window {}
 
-fn class_from_html(mut body: String) -> (String, String, bool) {
-    let mut ret: &str = "";
-    let mut retstring = body.clone();
-    let has_image: bool;
-    if body.contains("<br><img src=\"file:///") {
-        has_image = true;
-        let split = retstring.split_once("<br><img src=\"file:///").unwrap();
-        body = split.0.to_string() + "sent an image.".into();
-        retstring = split.1.to_string();
-        let split = retstring.split_once("\"");
-        if split.is_some() {
-            ret = split.unwrap().0;
+fn set_actions(
+    notibox: &crate::notibox::imp::NotiBox,
+    notification: &Notification,
+    window: &Window,
+    id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>,
+) {
+    let id = notification.replaces_id;
+    let resident = notification.resident();
+    notibox.resident.set(resident);
+    let action_box = notibox.action_box.borrow();
+    while let Some(child) = action_box.first_child() {
+        action_box.remove(&child);
+    }
+    notibox.has_default_action.set(false);
+    for action in notification.parsed_actions() {
+        if action.is_default() {
+            notibox.has_default_action.set(true);
+            continue;
         }
-    } else {
-        has_image = false;
-        let mut open = false;
-        for char in body.chars() {
-            if char == '<' && !open {
-                open = true;
-            } else if open {
-                ret = match char {
-                    'u' => "underline",
-                    _ => {
-                        ret = "";
-                        break;
-                    }
-                };
-                break;
-            }
+        if action.is_inline_reply() {
+            continue;
         }
-        body.remove_matches("<u>");
-        body.remove_matches("</u>");
+        let button = if notification.action_icons() {
+            let button = Button::from_icon_name(&action.key);
+            button.set_tooltip_text(Some(&action.label));
+            button
+        } else {
+            Button::with_label(&action.label)
+        };
+        button.set_css_classes(&["ActionButton"]);
+        let key = action.key;
+        button.connect_clicked(clone!(@weak window, @weak id_map => move |_| {
+            invoke_action(id, key.clone(), resident, &window, id_map);
+        }));
+        action_box.append(&button);
+    }
+    action_box.set_visible(action_box.first_child().is_some());
+    if notibox.has_default_action.get() {
+        notibox.obj().add_css_class("DefaultAction");
+    } else {
+        notibox.obj().remove_css_class("DefaultAction");
+    }
+}
+
+fn set_category(notibox: &crate::notibox::imp::NotiBox, category: Option<&str>) {
+    let obj = notibox.obj();
+    for class in notibox.category_classes.take() {
+        obj.remove_css_class(&class);
+    }
+    let Some(category) = category else {
+        return;
+    };
+    // "im.received" gets both "category-im" and "category-im-received"
+    let mut classes = Vec::new();
+    if let Some((class, _)) = category.split_once('.') {
+        classes.push(format!("category-{}", class));
+    }
+    classes.push(format!("category-{}", category.replace('.', "-")));
+    for class in classes.iter() {
+        obj.add_css_class(class);
+    }
+    notibox.category_classes.replace(classes);
+}
+
+fn set_time(notibox: &crate::notibox::imp::NotiBox) {
+    let received = notibox.received.get();
+    let label = notibox.time.borrow();
+    label.set_text(&time::relative_time(received));
+    label.set_tooltip_text(Some(&time::absolute_time(received)));
+}
+
+fn set_body(label: &Label, body: &markup::Markup) {
+    if body.text.trim().is_empty() && !body.images.is_empty() {
+        label.set_text("sent an image.");
+    } else {
+        label.set_markup(&body.markup);
+    }
+    let links: Vec<String> = body
+        .links
+        .iter()
+        .map(|link| format!("{}: {}", link.text, link.href))
+        .collect();
+    if links.is_empty() {
+        label.set_tooltip_text(None);
+    } else {
+        label.set_tooltip_text(Some(&links.join("\n")));
     }
-    (body, String::from(ret), has_image)
 }
 
 fn set_image(
@@ -521,12 +1129,13 @@ fn set_image(
     picture: &String,
     icon: &String,
     image: &Image,
+    size: i32,
 ) -> bool {
     let mut pixbuf: Option<Pixbuf> = None;
     let resize_pixbuf = |pixbuf: Option<Pixbuf>| {
             pixbuf
                 .unwrap()
-                .scale_simple(100, 100, gdk_pixbuf::InterpType::Bilinear)
+                .scale_simple(size, size, gdk_pixbuf::InterpType::Bilinear)
     };
     let use_icon = |mut _pixbuf: Option<Pixbuf>| {
         if Path::new(&icon).is_file() {
@@ -543,7 +1152,7 @@ fn set_image(
 
     if picture != "" {
         if Path::new(&picture).is_file() {
-            pixbuf = Some(Pixbuf::from_file_at_size(picture, 100, 100).unwrap());
+            pixbuf = Some(Pixbuf::from_file_at_size(picture, size, size).unwrap());
             image.set_from_pixbuf(Some(&pixbuf.unwrap()));
             image.style_context().add_class("picture");
             return true;
@@ -551,24 +1160,30 @@ fn set_image(
             (use_icon)(pixbuf);
             return true;
         }
-    } else if icon != "" {
+    }
+    match data.normalize() {
+        Ok(data) => {
+            let bytes = gtk::glib::Bytes::from(&data.data);
+            pixbuf = Some(Pixbuf::from_bytes(
+                &bytes,
+                gdk_pixbuf::Colorspace::Rgb,
+                data.has_alpha,
+                data.bits_per_sample,
+                data.width,
+                data.height,
+                data.rowstride,
+            ));
+            pixbuf = resize_pixbuf(pixbuf);
+            image.set_from_pixbuf(Some(&pixbuf.unwrap()));
+            image.style_context().add_class("picture");
+            return true;
+        }
+        Err(ImageDataError::Empty) => (),
+        Err(error) => eprintln!("Ignoring image data: {}", error),
+    }
+    if icon != "" {
         (use_icon)(pixbuf);
         return true;
-    } else if data.width != -1 {
-        let bytes = gtk::glib::Bytes::from(&data.data);
-        pixbuf = Some(Pixbuf::from_bytes(
-            &bytes,
-            gdk_pixbuf::Colorspace::Rgb,
-            data.has_alpha,
-            data.bits_per_sample,
-            data.width,
-            data.height,
-            data.rowstride,
-        ));
-        pixbuf = resize_pixbuf(pixbuf);
-        image.set_from_pixbuf(Some(&pixbuf.unwrap()));
-        image.style_context().add_class("picture");
-        return true;
     }
     false
 }
@@ -579,15 +1194,21 @@ pub fn activate_inline_reply(
     window: &Window,
     id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>,
 ) {
-    thread::spawn(move || {
-        let conn = Connection::new_session().unwrap();
-        let proxy = conn.with_proxy(
-            "org.freedesktop.Notifications",
-            "/org/freedesktop/Notifications",
-            Duration::from_millis(1000),
-        );
-        let _: Result<(), dbus::Error> =
-            proxy.method_call("org.freedesktop.Notifications", "InlineReply", (id, text));
-    });
+    window.client().inline_reply(id, text);
     window.delete_specific_notification_with_id(id, id_map)
 }
+
+pub fn invoke_action(
+    id: u32,
+    key: String,
+    resident: bool,
+    window: &Window,
+    id_map: Rc<RefCell<HashMap<u32, Rc<NotificationButton>>>>,
+) {
+    // the worker sends requests in order, so the action is invoked before the close
+    window.client().invoke_action(id, key);
+    if !resident {
+        window.client().dismiss(id);
+        window.remove_notification_with_id(id, id_map)
+    }
+}